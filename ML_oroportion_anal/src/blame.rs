@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Mutex}, thread};
+use anyhow::{anyhow, Ok, Result};
+use serde::Serialize;
+
+use crate::{count::{classify_reader, LanguageStats, LinePart}, counter::Counter, embedded::{embedded_name, Embedding}, git::git, lang::{Language, Languages}, origin::{ContentScan, GENERATED_BUCKET}, testcode::{is_test_path, test_name}};
+
+/// # BlameKey
+/// * Author: 按作者（名称和邮箱）汇总
+/// * Domain: 按邮箱的域名汇总，例如`example.com`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum BlameKey{
+    #[default]
+    Author,
+    Domain,
+}
+
+/// # AuthorStats
+/// 一个作者（或域名）负责的行数，按`git blame`中每一行最后修改的作者计算
+/// * author: 作者或域名
+/// * total: 合计，files为包含该作者至少一行的文件数
+/// * languages: 各语言的行数，与按语言汇总相同，测试代码、嵌入的代码、生成的代码与第三方代码单独汇总
+/// * directories: 各文件夹的行数，文件夹为指定目录（仓库根目录或子文件夹）下depth层的文件夹，指定的目录为`.`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AuthorStats{
+    pub author: String,
+    pub total: LanguageStats,
+    pub languages: BTreeMap<String, LanguageStats>,
+    pub directories: BTreeMap<PathBuf, LanguageStats>,
+}
+
+impl Counter {
+    /// 对仓库中某个版本需要统计的文件执行`git blame`，按作者或域名汇总代码、注释、空行，
+    /// 每一行的分类与普通统计相同（Jupyter notebook除外，不参与blame）。mailmap为额外的mailmap文件，仓库中的`.mailmap`总是生效
+    pub fn blame(&self, repo: &Path, rev: &str, key: BlameKey, mailmap: Option<&Path>, depth: usize)->Result<Vec<AuthorStats>>{
+        let files = self.tracked_files(repo, rev)?;
+        let next = AtomicUsize::new(0);
+        let authors: Mutex<BTreeMap<String, AuthorStats>> = Mutex::new(BTreeMap::new());
+        thread::scope(|scope| {
+            let workers = (0..self.thread_count()).map(|_| scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((entry, language, hint)) = files.get(index) else { break };
+                    if language.embedded == Some(Embedding::Notebook){
+                        continue;
+                    }
+                    let Some((stats, scan)) = blame_file(repo, rev, &entry.path, language, self.language_defs(), key, mailmap)? else { continue };
+                    let origin = hint.finish(&scan);
+                    if !self.keeps(origin){
+                        continue;
+                    }
+                    let dir = entry.path.parent().unwrap_or(Path::new("")).iter().take(depth).collect::<PathBuf>();
+                    let dir = if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir };
+                    let mut authors = authors.lock().map_err(|_| anyhow!("blame线程异常退出"))?;
+                    for (author, parts) in stats{
+                        let entry = authors.entry(author.clone()).or_insert_with(|| AuthorStats{ author, ..Default::default() });
+                        let mut total = LanguageStats{ files: 1, ..Default::default() };
+                        for (name, stats) in parts{
+                            let name = if origin.is_source() { name } else { GENERATED_BUCKET.to_string() };
+                            *entry.languages.entry(name).or_default() += LanguageStats{ files: 1, ..stats };
+                            total += stats;
+                        }
+                        entry.total += total;
+                        *entry.directories.entry(dir.clone()).or_default() += total;
+                    }
+                }
+                Ok(())
+            })).collect::<Vec<_>>();
+            for worker in workers{
+                worker.join().map_err(|_| anyhow!("blame线程异常退出"))??;
+            }
+            Ok(())
+        })?;
+        let mut authors = authors.into_inner().map_err(|_| anyhow!("blame线程异常退出"))?.into_values().collect::<Vec<_>>();
+        authors.sort_by(|a, b| b.total.code.cmp(&a.total.code).then_with(|| a.author.cmp(&b.author)));
+        Ok(authors)
+    }
+}
+
+/// 一个文件中每个作者在各语言（包含`test_name`标记的测试代码与`embedded_name`标记的嵌入代码）中的行数，
+/// 与`FileStats::parts`相同，测试文件中嵌入的代码也是测试代码
+type FileBlame = BTreeMap<String, BTreeMap<String, LanguageStats>>;
+
+/// blame一个文件，返回每个作者的行数以及内容特征，二进制文件返回None。
+/// 先从porcelain输出中还原文件内容，再按普通统计的方式（编码识别、换行、代码块、测试代码）分类每一行
+fn blame_file(repo: &Path, rev: &str, path: &Path, language: &Language, languages: &Languages, key: BlameKey, mailmap: Option<&Path>)->Result<Option<(FileBlame, ContentScan)>>{
+    let mailmap = mailmap.map(|m| format!("mailmap.file={}", m.display()));
+    let path = path.to_string_lossy();
+    let mut args = Vec::new();
+    if let Some(mailmap) = mailmap.as_deref(){
+        args.extend(["-c", mailmap]);
+    }
+    args.extend(["blame", "--line-porcelain", rev, "--", &path]);
+    let out = git(repo, &args)?;
+    // git按`\n`拆分的每一行的作者，以及还原的文件内容
+    let (mut authors, mut content) = (Vec::new(), Vec::new());
+    let (mut name, mut mail) = (String::new(), String::new());
+    for line in out.split(|b| *b == b'\n'){
+        if let Some(line) = line.strip_prefix(b"\t"){
+            authors.push(match key {
+                BlameKey::Author => format!("{} {}", name, mail),
+                BlameKey::Domain => mail.trim_matches(['<', '>']).rsplit_once('@').map_or("unknown", |(_, domain)| domain).to_lowercase(),
+            });
+            content.extend_from_slice(line);
+            content.push(b'\n');
+        }else if let Some(value) = line.strip_prefix(b"author "){
+            name = String::from_utf8_lossy(value).to_string();
+        }else if let Some(value) = line.strip_prefix(b"author-mail "){
+            mail = String::from_utf8_lossy(value).to_string();
+        }
+    }
+    let std::result::Result::Ok((scanned, classes)) = classify_reader(&content[..], language, languages) else { return Ok(None) };
+    let whole_test = is_test_path(Path::new(path.as_ref()));
+    let mut res = FileBlame::new();
+    for class in classes{
+        // UTF-16的内容中git看到的换行可能与解码后不同，超出时归属到最后一行的作者
+        let Some(author) = authors.get(class.line).or(authors.last()) else { break };
+        let name = match class.part {
+            LinePart::Embedded(name) if whole_test => test_name(name),
+            LinePart::Embedded(name) => embedded_name(name),
+            LinePart::Test => test_name(&language.name),
+            LinePart::Source if whole_test => test_name(&language.name),
+            LinePart::Source => language.name.clone(),
+        };
+        res.entry(author.clone()).or_default().entry(name).or_default().count(class.kind);
+    }
+    Ok(Some((res, scanned.scan)))
+}
+
+#[cfg(test)]
+mod test{
+    use std::{fs, path::PathBuf};
+
+    use super::BlameKey;
+    use crate::{counter::Counter, git::test::{init_repo, run_git}};
+
+    #[test]
+    fn test_blame(){
+        let repo = init_repo("blame");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/main.rs"), "// alice\nfn main(){}\n").unwrap();
+        fs::write(repo.join("build.py"), "print(1)\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "alice", "--author", "Alice <alice@corp.com>"]);
+        fs::write(repo.join("src/main.rs"), "// alice\nfn main(){}\n\nfn bob(){}\n").unwrap();
+        run_git(&repo, &["commit", "-q", "-am", "bob", "--author", "Bob <bob@old.org>"]);
+        fs::write(repo.join("build.py"), "print(1)\nprint(2)\n").unwrap();
+        run_git(&repo, &["commit", "-q", "-am", "bob2", "--author", "Robert <bob@corp.com>"]);
+        let mailmap = repo.join(".git/test_mailmap");
+        fs::write(&mailmap, "Bob <bob@corp.com> <bob@old.org>\nBob <bob@corp.com> Robert <bob@corp.com>\n").unwrap();
+        let counter = Counter::new();
+        let plain = counter.blame(&repo, "HEAD", BlameKey::Author, None, 1).unwrap();
+        let mapped = counter.blame(&repo, "HEAD", BlameKey::Author, Some(&mailmap), 1).unwrap();
+        let domains = counter.blame(&repo, "HEAD", BlameKey::Domain, Some(&mailmap), 1).unwrap();
+        // 指定子文件夹时只blame其中的文件
+        let src = counter.blame(&repo.join("src"), "HEAD", BlameKey::Author, None, 1).unwrap();
+        assert_eq!(3, plain.len());
+        assert_eq!(vec!["Alice <alice@corp.com>", "Bob <bob@corp.com>"], mapped.iter().map(|a| a.author.as_str()).collect::<Vec<_>>());
+        let (alice, bob) = (&mapped[0], &mapped[1]);
+        assert_eq!((2, 1, 0, 2), (alice.total.code, alice.total.comment, alice.total.blank, alice.total.files));
+        assert_eq!((2, 0, 1, 2), (bob.total.code, bob.total.comment, bob.total.blank, bob.total.files));
+        assert_eq!(1, bob.languages["Python"].code);
+        assert_eq!(1, bob.directories[&PathBuf::from("src")].code);
+        assert_eq!(1, bob.directories[&PathBuf::from(".")].code);
+        assert_eq!(vec!["corp.com"], domains.iter().map(|a| a.author.as_str()).collect::<Vec<_>>());
+        assert_eq!(4, domains[0].total.code);
+        assert_eq!(vec![(2, 1), (2, 1)], src.iter().map(|a| (a.total.lines(), a.total.files)).collect::<Vec<_>>());
+        assert_eq!(vec![&PathBuf::from(".")], src[1].directories.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_blame_encoding(){
+        let repo = init_repo("blame_encoding");
+        let utf16 = "\u{feff}// a\r\nfn a(){}\r\n".encode_utf16().flat_map(|u| u.to_le_bytes()).collect::<Vec<_>>();
+        fs::write(repo.join("wide.rs"), utf16).unwrap();
+        fs::write(repo.join("bom.rs"), "\u{feff}fn b(){}\r\n\r\n#[cfg(test)]\r\nmod test{}\r\n").unwrap();
+        fs::write(repo.join("image.rs"), b"\x89PNG\r\n\x1a\n\x00\x00").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "alice", "--author", "Alice <alice@corp.com>"]);
+        fs::write(repo.join("bom.rs"), "\u{feff}fn b(){}\r\n// bob\r\n#[cfg(test)]\r\nmod test{}\r\n").unwrap();
+        run_git(&repo, &["commit", "-q", "-am", "bob", "--author", "Bob <bob@corp.com>"]);
+        let authors = Counter::new().blame(&repo, "HEAD", BlameKey::Author, None, 1).unwrap();
+        assert_eq!(vec!["Alice <alice@corp.com>", "Bob <bob@corp.com>"], authors.iter().map(|a| a.author.as_str()).collect::<Vec<_>>());
+        let (alice, bob) = (&authors[0], &authors[1]);
+        assert_eq!((4, 1, 0, 2), (alice.total.code, alice.total.comment, alice.total.blank, alice.total.files));
+        assert_eq!(2, alice.languages["Rust (test)"].code);
+        assert_eq!((0, 1, 1), (bob.total.code, bob.total.comment, bob.total.files));
+    }
+}
@@ -0,0 +1,200 @@
+use std::{collections::{BTreeMap, HashMap}, path::{Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+
+use crate::{complexity::Complexity, count::LanguageStats, embedded::embedded_name, origin::{Origin, GENERATED_BUCKET}, output::SortKey, testcode::test_name};
+
+/// # Breakdown
+/// * Language: 按语言汇总（默认）
+/// * File: 按文件输出，配合`--top`查看最大的文件
+/// * Directory: 按文件夹汇总，配合`--depth`限制文件夹的层级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Breakdown{
+    #[default]
+    Language,
+    File,
+    #[cfg_attr(feature = "cli", value(alias = "dir"))]
+    Directory,
+}
+
+/// # FileStats
+/// 单个文件的统计结果
+/// * path: 输出时使用的路径，由命令行中的路径与相对路径拼接而成
+/// * root: path所在的根目录，按文件夹汇总时只计算根目录以下的层级
+/// * language: 文件的语言
+/// * stats: 文件本身语言的行数，不包含嵌入的代码，files固定为1
+/// * origin: 手写、生成或第三方代码
+/// * embedded: Markdown代码块、notebook代码单元等嵌入的代码按语言统计的行数
+/// * test: stats中属于测试代码的行数，整个文件都是测试代码时与stats相同，没有测试代码时为空
+/// * complexity: 函数的复杂度，只在`Counter::complexity`打开并且语言有`complexity`规则时计算
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStats{
+    pub path: PathBuf,
+    pub root: PathBuf,
+    pub language: String,
+    pub stats: LanguageStats,
+    #[serde(default, skip_serializing_if = "Origin::is_source")]
+    pub origin: Origin,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub embedded: BTreeMap<String, LanguageStats>,
+    #[serde(default, skip_serializing_if = "LanguageStats::is_empty")]
+    pub test: LanguageStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<Complexity>,
+}
+
+impl FileStats {
+    /// 相对于根目录的路径，path不在root下时为path本身
+    pub fn relative_path(&self)->&Path{
+        self.path.strip_prefix(&self.root).unwrap_or(&self.path)
+    }
+
+    /// 包含嵌入代码的合计，files为1
+    pub fn total(&self)->LanguageStats{
+        let mut total = self.stats;
+        for stats in self.embedded.values(){
+            total += LanguageStats{ files: 0, ..*stats };
+        }
+        total
+    }
+
+    /// 按语言汇总时的名称与行数：文件本身的语言、`test_name`标记的测试代码以及`embedded_name`标记的嵌入代码，
+    /// 同时包含测试与非测试代码的文件在两项中都计为1个文件，生成的代码与第三方代码整个计入`GENERATED_BUCKET`。
+    /// 测试文件（test与stats相同）中嵌入的代码同样是测试代码，例如`tests/`下notebook的代码单元计入`Python (test)`。
+    /// 手动修改的json中test可能比stats大，此时非测试代码按0计算
+    pub fn parts(&self)->Vec<(String, LanguageStats)>{
+        if !self.origin.is_source(){
+            return vec![(GENERATED_BUCKET.to_string(), self.total())];
+        }
+        let whole_test = !self.test.is_empty() && self.test == self.stats;
+        let mut parts = Vec::new();
+        if self.test != self.stats{
+            let (stats, test) = (self.stats, self.test);
+            parts.push((self.language.clone(), LanguageStats{ files: 1, code: stats.code.saturating_sub(test.code), comment: stats.comment.saturating_sub(test.comment), blank: stats.blank.saturating_sub(test.blank) }));
+        }
+        if !self.test.is_empty(){
+            parts.push((test_name(&self.language), self.test));
+        }
+        parts.extend(self.embedded.iter().map(|(language, stats)| match whole_test {
+            true => (test_name(language), *stats),
+            false => (embedded_name(language), *stats),
+        }));
+        parts
+    }
+}
+
+/// # DirStats
+/// 一个文件夹（包含子文件夹）的统计结果
+/// * path: 文件夹路径
+/// * total: 所有语言的合计
+/// * languages: 每种语言的行数，按排序方式排列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirStats{
+    pub path: PathBuf,
+    pub total: LanguageStats,
+    pub languages: Vec<(String, LanguageStats)>,
+}
+
+/// 按语言汇总，嵌入的代码、生成的代码与第三方代码单独汇总
+pub fn by_language(files: &[FileStats])->HashMap<String, LanguageStats>{
+    let mut state: HashMap<String, LanguageStats> = HashMap::new();
+    for file in files.iter(){
+        for (name, stats) in file.parts(){
+            *state.entry(name).or_default() += stats;
+        }
+    }
+    state
+}
+
+/// 按排序方式输出文件，数值相同时按路径排序，top限制输出的数量
+pub fn top_files(files: &[FileStats], sort: SortKey, top: Option<usize>)->Vec<&FileStats>{
+    let mut files = files.iter().collect::<Vec<_>>();
+    files.sort_by(|a, b| sort.value(&b.total()).cmp(&sort.value(&a.total())).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(top.unwrap_or(usize::MAX));
+    files
+}
+
+/// 按文件夹汇总，文件计入根目录下depth层的文件夹，depth为0时只按根目录汇总
+pub fn by_directory(files: &[FileStats], depth: usize, sort: SortKey, top: Option<usize>)->Vec<DirStats>{
+    let mut dirs: BTreeMap<PathBuf, HashMap<String, LanguageStats>> = BTreeMap::new();
+    for file in files.iter(){
+        let relative = file.relative_path();
+        let prefix = relative.parent().unwrap_or(Path::new("")).iter().take(depth).collect::<PathBuf>();
+        let dir = match prefix.as_os_str().is_empty() {
+            true => file.root.clone(),
+            false => file.root.join(prefix),
+        };
+        let languages = dirs.entry(dir).or_default();
+        for (name, stats) in file.parts(){
+            *languages.entry(name).or_default() += stats;
+        }
+    }
+    let mut dirs = dirs.into_iter().map(|(path, languages)| {
+        let mut total = LanguageStats::default();
+        for stats in languages.values(){
+            total += *stats;
+        }
+        let mut languages = languages.into_iter().collect::<Vec<_>>();
+        languages.sort_by(|a, b| sort.value(&b.1).cmp(&sort.value(&a.1)).then_with(|| a.0.cmp(&b.0)));
+        DirStats{ path, total, languages }
+    }).collect::<Vec<_>>();
+    dirs.sort_by(|a, b| sort.value(&b.total).cmp(&sort.value(&a.total)).then_with(|| a.path.cmp(&b.path)));
+    dirs.truncate(top.unwrap_or(usize::MAX));
+    dirs
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::PathBuf;
+
+    use super::{by_directory, top_files, FileStats};
+    use crate::{count::LanguageStats, output::SortKey};
+
+    fn file(path: &str, language: &str, code: u64)->FileStats{
+        FileStats{
+            path: PathBuf::from(path),
+            root: PathBuf::from("repo"),
+            language: language.to_string(),
+            stats: LanguageStats{ files: 1, code, comment: 0, blank: 0 },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_breakdown(){
+        let files = vec![
+            file("repo/Cargo.toml", "TOML", 10),
+            file("repo/crates/foo/src/lib.rs", "Rust", 120),
+            file("repo/crates/foo/Cargo.toml", "TOML", 3),
+            file("repo/crates/bar/src/main.rs", "Rust", 40),
+        ];
+        let top = top_files(&files, SortKey::Code, Some(2));
+        assert_eq!(vec![PathBuf::from("repo/crates/foo/src/lib.rs"), PathBuf::from("repo/crates/bar/src/main.rs")], top.iter().map(|f| f.path.clone()).collect::<Vec<_>>());
+        let dirs = by_directory(&files, 2, SortKey::Code, None);
+        assert_eq!(3, dirs.len());
+        assert_eq!(PathBuf::from("repo/crates/foo"), dirs[0].path);
+        assert_eq!(123, dirs[0].total.code);
+        assert_eq!(("Rust".to_string(), 120), (dirs[0].languages[0].0.clone(), dirs[0].languages[0].1.code));
+        assert_eq!(PathBuf::from("repo"), dirs[2].path);
+        let dirs = by_directory(&files, 0, SortKey::Code, None);
+        assert_eq!(1, dirs.len());
+        assert_eq!(4, dirs[0].total.files);
+        assert_eq!(2, dirs[0].languages.len());
+    }
+
+    #[test]
+    fn test_parts(){
+        let mut lib = file("repo/src/lib.rs", "Rust", 10);
+        lib.test = LanguageStats{ files: 1, code: 4, comment: 0, blank: 0 };
+        assert_eq!(vec![6, 4], lib.parts().iter().map(|(_, s)| s.code).collect::<Vec<_>>());
+        // test比stats大时不会溢出
+        lib.test = LanguageStats{ files: 1, code: 12, comment: 1, blank: 0 };
+        assert_eq!(vec![0, 12], lib.parts().iter().map(|(_, s)| s.code).collect::<Vec<_>>());
+        let mut notebook = file("repo/analysis.ipynb", "Jupyter Notebook", 0);
+        notebook.embedded.insert("Python".to_string(), LanguageStats{ files: 1, code: 3, comment: 0, blank: 0 });
+        assert_eq!(vec!["Jupyter Notebook", "Python (embedded)"], notebook.parts().into_iter().map(|(name, _)| name).collect::<Vec<_>>());
+        // 测试文件中嵌入的代码也是测试代码
+        notebook.test = notebook.stats;
+        assert_eq!(vec![("Jupyter Notebook (test)".to_string(), 0), ("Python (test)".to_string(), 3)], notebook.parts().into_iter().map(|(name, s)| (name, s.code)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::PathBuf;
+use clap::{builder::RangedU64ValueParser, Parser, ValueEnum};
+use walkfile::HiddenPolicy;
+
+use ml_oroportion_anal::{BlameKey, Breakdown, HistoryStep, OutputFormat, SortKey};
+
+/// 统计指定目录下不同语言的代码行数
+#[derive(Debug, Parser)]
+#[command(name = "ML_oroportion_anal", version, about)]
+pub struct Cli{
+    /// 要统计的目录或文件，可以指定多个
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+
+    /// 排除匹配的文件或文件夹，gitignore风格的通配符，例如`target/`、`*.min.js`，可以多次指定
+    #[arg(short, long = "exclude", value_name = "GLOB")]
+    pub excludes: Vec<String>,
+
+    /// 不读取`.gitignore`、`.ignore`忽略文件
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// 不统计生成的代码与第三方代码（默认单独汇总为`Generated/Vendored`）
+    #[arg(long)]
+    pub exclude_generated: bool,
+
+    /// 只统计指定的语言，例如`Rust`，可以多次指定
+    #[arg(short = 'l', long = "lang", value_name = "LANG")]
+    pub langs: Vec<String>,
+
+    /// 新增或覆盖语言定义的toml、json文件，可以多次指定
+    #[arg(long = "languages", value_name = "FILE")]
+    pub language_files: Vec<PathBuf>,
+
+    /// 输出格式
+    #[arg(short, long, value_enum, default_value_t)]
+    pub output: OutputFormat,
+
+    /// 排序方式，数值从大到小，名称从小到大
+    #[arg(short, long, value_enum, default_value_t)]
+    pub sort: SortKey,
+
+    /// 统计本地git仓库中某个版本（分支、标签或提交）跟踪的文件，不需要检出，此时只能指定一个仓库
+    #[arg(long, value_name = "REV")]
+    pub rev: Option<String>,
+
+    /// 统计git仓库中`--rev`（默认为HEAD）沿第一个父提交的历史，按`--step`选取提交
+    #[arg(long, conflicts_with_all = ["blame", "diff", "save", "check", "baseline", "by", "top", "complexity"])]
+    pub history: bool,
+
+    /// 统计历史时选取提交的方式：每N个提交取一个，或者每天（day）、每周（week）取最后一个提交
+    #[arg(long, value_name = "N|day|week", default_value = "1")]
+    pub step: HistoryStep,
+
+    /// 统计历史时使用的缓存文件，默认放在仓库的git目录中
+    #[arg(long, value_name = "FILE")]
+    pub cache: Option<PathBuf>,
+
+    /// 对git仓库中`--rev`（默认为HEAD）的文件执行`git blame`，按作者或邮箱域名汇总行数
+    #[arg(long, value_enum, value_name = "KEY", conflicts_with_all = ["diff", "save", "check", "baseline", "by", "top", "complexity"])]
+    pub blame: Option<BlameKey>,
+
+    /// blame时额外使用的mailmap文件，把同一个人的不同名称、邮箱合并，仓库中的`.mailmap`总是生效
+    #[arg(long, value_name = "FILE")]
+    pub mailmap: Option<PathBuf>,
+
+    /// 比较两个目录或两个`--save`保存的json文件，输出各语言以及文件的变化
+    #[arg(long, conflicts_with_all = ["rev", "save", "check", "baseline", "by", "top", "complexity"])]
+    pub diff: bool,
+
+    /// 把统计结果保存为json文件，之后可以用`--diff`比较
+    #[arg(long, value_name = "FILE")]
+    pub save: Option<PathBuf>,
+
+    /// 按toml或json规则文件检查统计结果，有违规时退出码为3，用于CI
+    #[arg(long, value_name = "POLICY", conflicts_with_all = ["by", "top", "complexity"])]
+    pub check: Option<PathBuf>,
+
+    /// 检查时用于比较的基线（`--save`保存的json文件），覆盖规则文件中的baseline
+    #[arg(long, value_name = "FILE", requires = "check")]
+    pub baseline: Option<PathBuf>,
+
+    /// 汇总方式：按语言、按文件或按文件夹
+    #[arg(long, value_enum, default_value_t)]
+    pub by: Breakdown,
+
+    /// 按文件或文件夹汇总或者`--complexity`时只输出排在前面的N项
+    #[arg(long, value_name = "N")]
+    pub top: Option<usize>,
+
+    /// 按文件夹汇总或blame时文件夹的层级，0表示只按指定的目录汇总
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub depth: usize,
+
+    /// 计算Rust、Python、Go、Java函数的复杂度，输出各语言的平均值以及圈复杂度最高的`--top`（默认10）个文件和函数，不能与`--by`同时使用
+    #[arg(long, conflicts_with = "by")]
+    pub complexity: bool,
+
+    /// 并发统计的线程数，默认为CPU核数
+    #[arg(short = 'j', long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub threads: Option<usize>,
+
+    /// 隐藏文件（以`.`开头）的处理方式
+    #[arg(long, value_enum, default_value_t = HiddenMode::Exclude)]
+    pub hidden: HiddenMode,
+
+    /// 排除隐藏文件时仍然统计的名称，例如`.github`，可以多次指定
+    #[arg(long = "keep-hidden", value_name = "NAME")]
+    pub keep_hidden: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HiddenMode{
+    Include,
+    Exclude,
+}
+
+impl Cli {
+    pub fn hidden_policy(&self)->HiddenPolicy{
+        match self.hidden {
+            HiddenMode::Include => HiddenPolicy::Include,
+            HiddenMode::Exclude if self.keep_hidden.is_empty() => HiddenPolicy::Exclude,
+            HiddenMode::Exclude => HiddenPolicy::ExcludeExcept(self.keep_hidden.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::PathBuf;
+
+    use clap::Parser;
+    use walkfile::HiddenPolicy;
+
+    use super::Cli;
+    use ml_oroportion_anal::{BlameKey, Breakdown, HistoryStep, OutputFormat, SortKey};
+
+    #[test]
+    fn test_parse(){
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", "src", "tests", "-e", "target/", "--lang", "Rust", "-o", "json", "--sort", "name", "--keep-hidden", ".github"]).unwrap();
+        assert_eq!(2, cli.paths.len());
+        assert_eq!(vec!["target/".to_string()], cli.excludes);
+        assert_eq!(OutputFormat::Json, cli.output);
+        assert_eq!(SortKey::Name, cli.sort);
+        assert_eq!(HiddenPolicy::ExcludeExcept(vec![".github".to_string()]), cli.hidden_policy());
+        assert!(!cli.no_ignore);
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--no-ignore"]).unwrap().no_ignore);
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--exclude-generated"]).unwrap().exclude_generated);
+        assert_eq!(Some(4), Cli::try_parse_from(["ML_oroportion_anal", ".", "-j", "4"]).unwrap().threads);
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--threads", "0"]).is_err());
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", ".", "--by", "dir", "--depth", "2", "--top", "10"]).unwrap();
+        assert_eq!((Breakdown::Directory, 2, Some(10)), (cli.by, cli.depth, cli.top));
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", "--diff", "v1.json", "v2"]).unwrap().diff);
+        assert_eq!(Some("v1.2".to_string()), Cli::try_parse_from(["ML_oroportion_anal", ".", "--rev", "v1.2"]).unwrap().rev);
+        assert_eq!(Some(PathBuf::from("out.json")), Cli::try_parse_from(["ML_oroportion_anal", ".", "--save", "out.json"]).unwrap().save);
+        // --diff、--history、--blame不会保存、检查或按其他方式汇总，同时指定时报错而不是忽略
+        let conflicts: [&[&str]; 9] = [
+            &["--diff", "--save", "out.json"], &["--diff", "--rev", "HEAD"], &["--diff", "--by", "file"],
+            &["--history", "--save", "out.json"], &["--history", "--check", "policy.toml"], &["--history", "--blame", "author"],
+            &["--blame", "author", "--top", "10"], &["--blame", "author", "--complexity"], &["--check", "policy.toml", "--by", "file"],
+        ];
+        for args in conflicts{
+            assert!(Cli::try_parse_from(["ML_oroportion_anal", "v1.json", "v2"].iter().chain(args)).is_err(), "{:?}", args);
+        }
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--baseline", "main.json"]).is_err());
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", ".", "--history", "--step", "week"]).unwrap();
+        assert_eq!((true, HistoryStep::Week), (cli.history, cli.step));
+        assert_eq!(HistoryStep::Every(1), Cli::try_parse_from(["ML_oroportion_anal", "."]).unwrap().step);
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--step", "month"]).is_err());
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", ".", "--blame", "domain", "--mailmap", "mailmap"]).unwrap();
+        assert_eq!((Some(BlameKey::Domain), Some(PathBuf::from("mailmap"))), (cli.blame, cli.mailmap));
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--blame", "commit"]).is_err());
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", ".", "--check", "policy.toml", "--baseline", "main.json"]).unwrap();
+        assert_eq!((Some(PathBuf::from("policy.toml")), Some(PathBuf::from("main.json"))), (cli.check, cli.baseline));
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", ".", "--complexity", "--top", "5"]).unwrap();
+        assert_eq!((true, Some(5)), (cli.complexity, cli.top));
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "--complexity", "--by", "file"]).is_err());
+        assert!(Cli::try_parse_from(["ML_oroportion_anal"]).is_err());
+        assert!(Cli::try_parse_from(["ML_oroportion_anal", ".", "-o", "xml"]).is_err());
+    }
+}
@@ -0,0 +1,558 @@
+use std::{collections::BTreeMap, path::PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::breakdown::FileStats;
+
+/// # Blocks
+/// 划分代码块的方式
+/// * Braces: 用`{}`划分（rust、go、java）
+/// * Indent: 用缩进以及行尾的`:`划分（python）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Blocks{
+    #[default]
+    Braces,
+    Indent,
+}
+
+/// # ComplexityRules
+/// 计算复杂度的规则，只对注释与字符串以外的代码生效
+/// * blocks: 划分代码块的方式
+/// * functions: 声明函数的关键字，例如`fn`、`func`、`def`；为空时`{`前为`名称(参数)`的声明是函数（java的方法）
+/// * branches: 每出现一次圈复杂度加1的关键字与运算符，例如`if`、`for`、`&&`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComplexityRules{
+    pub blocks: Blocks,
+    pub functions: Vec<String>,
+    pub branches: Vec<String>,
+}
+
+/// # FunctionComplexity
+/// 一个函数的复杂度，函数中定义的闭包、匿名函数、内部类计入外层函数
+/// * name: 函数名，没有名称时为`<anonymous>`
+/// * line: 函数名所在的行，从1开始
+/// * cyclomatic: 近似的圈复杂度，1加上函数中`branches`出现的次数
+/// * nesting: 函数中代码块的最大嵌套层数，函数体本身为0
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionComplexity{
+    pub name: String,
+    pub line: u64,
+    pub cyclomatic: u64,
+    pub nesting: u64,
+}
+
+/// # Complexity
+/// 一个文件中所有函数的复杂度，按出现的顺序排列
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Complexity{
+    pub functions: Vec<FunctionComplexity>,
+}
+
+impl Complexity {
+    /// 文件中最深的嵌套层数
+    pub fn max_nesting(&self)->u64{
+        self.functions.iter().map(|f| f.nesting).max().unwrap_or_default()
+    }
+
+    /// 文件中所有函数的圈复杂度之和
+    pub fn cyclomatic(&self)->u64{
+        self.functions.iter().map(|f| f.cyclomatic).sum()
+    }
+}
+
+/// # LanguageComplexity
+/// 一种语言的复杂度合计，平均值由合计计算
+/// * files: 计算了复杂度的文件数
+/// * functions: 函数数
+/// * cyclomatic、nesting: 所有函数的圈复杂度、嵌套层数之和
+/// * max_cyclomatic、max_nesting: 单个函数的最大值
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LanguageComplexity{
+    pub language: String,
+    pub files: u64,
+    pub functions: u64,
+    pub cyclomatic: u64,
+    pub nesting: u64,
+    pub max_cyclomatic: u64,
+    pub max_nesting: u64,
+    pub avg_functions: f64,
+    pub avg_cyclomatic: f64,
+    pub avg_nesting: f64,
+}
+
+/// # FunctionOffender
+/// 复杂度排在前面的函数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionOffender{
+    pub path: PathBuf,
+    pub language: String,
+    #[serde(flatten)]
+    pub function: FunctionComplexity,
+}
+
+/// # FileComplexity
+/// 一个文件的复杂度合计
+/// * functions: 函数数
+/// * cyclomatic: 所有函数的圈复杂度之和
+/// * max_cyclomatic、max_nesting: 单个函数的最大值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileComplexity{
+    pub path: PathBuf,
+    pub language: String,
+    pub functions: u64,
+    pub cyclomatic: u64,
+    pub max_cyclomatic: u64,
+    pub max_nesting: u64,
+}
+
+/// # ComplexitySummary
+/// * languages: 各语言的平均复杂度，按函数数从多到少排列
+/// * files: 圈复杂度之和最高的文件，相同时单个函数最复杂的在前
+/// * functions: 圈复杂度最高的函数，相同时嵌套更深的在前
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ComplexitySummary{
+    pub languages: Vec<LanguageComplexity>,
+    pub files: Vec<FileComplexity>,
+    pub functions: Vec<FunctionOffender>,
+}
+
+/// 汇总文件的复杂度，top为输出的文件数与函数数。
+/// 没有计算复杂度的文件、生成的代码与第三方代码以及整个文件都是测试代码的文件不参与
+pub fn complexity_summary(files: &[FileStats], top: usize)->ComplexitySummary{
+    let mut languages: BTreeMap<&str, LanguageComplexity> = BTreeMap::new();
+    let mut functions = Vec::new();
+    let mut worst_files = Vec::new();
+    for file in files.iter(){
+        let Some(complexity) = &file.complexity else { continue };
+        if !file.origin.is_source() || (!file.test.is_empty() && file.test == file.stats){
+            continue;
+        }
+        worst_files.push(FileComplexity{
+            path: file.path.clone(),
+            language: file.language.clone(),
+            functions: complexity.functions.len() as u64,
+            cyclomatic: complexity.cyclomatic(),
+            max_cyclomatic: complexity.functions.iter().map(|f| f.cyclomatic).max().unwrap_or_default(),
+            max_nesting: complexity.max_nesting(),
+        });
+        let language = languages.entry(&file.language).or_insert_with(|| LanguageComplexity{ language: file.language.clone(), ..Default::default() });
+        language.files += 1;
+        for function in complexity.functions.iter(){
+            language.functions += 1;
+            language.cyclomatic += function.cyclomatic;
+            language.nesting += function.nesting;
+            language.max_cyclomatic = language.max_cyclomatic.max(function.cyclomatic);
+            language.max_nesting = language.max_nesting.max(function.nesting);
+            functions.push(FunctionOffender{ path: file.path.clone(), language: file.language.clone(), function: function.clone() });
+        }
+    }
+    let average = |sum: u64, count: u64| if count == 0 { 0.0 } else { sum as f64 / count as f64 };
+    let mut languages = languages.into_values().map(|l| LanguageComplexity{
+        avg_functions: average(l.functions, l.files),
+        avg_cyclomatic: average(l.cyclomatic, l.functions),
+        avg_nesting: average(l.nesting, l.functions),
+        ..l
+    }).collect::<Vec<_>>();
+    languages.sort_by(|a, b| b.functions.cmp(&a.functions).then_with(|| a.language.cmp(&b.language)));
+    functions.sort_by(|a, b| b.function.cyclomatic.cmp(&a.function.cyclomatic)
+        .then_with(|| b.function.nesting.cmp(&a.function.nesting))
+        .then_with(|| a.path.cmp(&b.path))
+        .then_with(|| a.function.line.cmp(&b.function.line)));
+    functions.truncate(top);
+    worst_files.retain(|f| f.functions > 0);
+    worst_files.sort_by(|a, b| b.cyclomatic.cmp(&a.cyclomatic)
+        .then_with(|| b.max_cyclomatic.cmp(&a.max_cyclomatic))
+        .then_with(|| a.path.cmp(&b.path)));
+    worst_files.truncate(top);
+    ComplexitySummary{ languages, files: worst_files, functions }
+}
+
+/// 不是函数声明的`名称(参数) {`，用于没有函数关键字的语言
+const NOT_FUNCTIONS: [&str; 16] = ["if", "for", "while", "switch", "catch", "synchronized", "try", "return", "new", "else", "do", "throw", "class", "interface", "enum", "record"];
+
+/// 正在统计的函数，base为函数开始前的代码块层数
+#[derive(Debug)]
+struct OpenFunction{
+    function: FunctionComplexity,
+    base: usize,
+}
+
+/// # ComplexityScanner
+/// 逐行计算复杂度，输入为去掉注释与字符串后的代码（`LineCounter::code`），每一行（包括空行、注释）都要输入
+pub(crate) struct ComplexityScanner<'a>{
+    rules: &'a ComplexityRules,
+    line: u64,
+    functions: Vec<FunctionComplexity>,
+    current: Option<OpenFunction>,
+    /// Braces：当前的`{`层数
+    depth: usize,
+    /// Braces：上一个`{`、`}`、`;`之后的代码，以及其中每一行开始的位置
+    header: String,
+    header_lines: Vec<(usize, u64)>,
+    /// Indent：打开的代码块所在行的缩进
+    blocks: Vec<usize>,
+    /// Indent：没有关闭的括号数，大于0时下一行是上一行的延续
+    brackets: usize,
+    /// Indent：当前逻辑行（包括延续的行）第一行的缩进
+    logical: usize,
+}
+
+impl<'a> ComplexityScanner<'a> {
+    pub(crate) fn new(rules: &'a ComplexityRules)->ComplexityScanner<'a>{
+        ComplexityScanner{ rules, line: 0, functions: Vec::new(), current: None, depth: 0, header: String::new(), header_lines: Vec::new(), blocks: Vec::new(), brackets: 0, logical: 0 }
+    }
+
+    pub(crate) fn line(&mut self, code: &[u8]){
+        self.line += 1;
+        let code = String::from_utf8_lossy(code);
+        match self.rules.blocks {
+            Blocks::Braces => self.braces(&code),
+            Blocks::Indent => self.indent(&code),
+        }
+    }
+
+    fn braces(&mut self, code: &str){
+        let branches = branch_positions(code, &self.rules.branches);
+        if !code.trim().is_empty(){
+            self.header_lines.push((self.header.len(), self.line));
+            self.header.push(' ');
+        }
+        for (i, c) in code.char_indices(){
+            if let Some(open) = self.current.as_mut(){
+                open.function.cyclomatic += branches.iter().filter(|p| **p == i).count() as u64;
+            }
+            match c {
+                '{' => {
+                    if self.current.is_none(){
+                        if let Some((name, position)) = function_name(&self.header, &self.rules.functions){
+                            let line = self.header_lines.iter().rev().find(|(start, _)| *start <= position).map_or(self.line, |(_, line)| *line);
+                            self.current = Some(OpenFunction{ function: FunctionComplexity{ name, line, cyclomatic: 1, nesting: 0 }, base: self.depth });
+                        }
+                    }
+                    self.depth += 1;
+                    if let Some(open) = self.current.as_mut(){
+                        open.function.nesting = open.function.nesting.max((self.depth - open.base - 1) as u64);
+                    }
+                    self.clear_header();
+                },
+                '}' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.current.as_ref().is_some_and(|open| open.base >= self.depth){
+                        self.close();
+                    }
+                    self.clear_header();
+                },
+                ';' => self.clear_header(),
+                _ => self.header.push(c),
+            }
+        }
+    }
+
+    fn clear_header(&mut self){
+        self.header.clear();
+        self.header_lines.clear();
+        self.header_lines.push((0, self.line));
+    }
+
+    fn indent(&mut self, code: &str){
+        if code.trim().is_empty(){
+            return;
+        }
+        if self.brackets == 0{
+            let indent = code.len() - code.trim_start().len();
+            while self.blocks.last().is_some_and(|b| *b >= indent){
+                self.blocks.pop();
+            }
+            if self.current.as_ref().is_some_and(|open| self.blocks.len() <= open.base){
+                self.close();
+            }
+            if self.current.is_none(){
+                if let Some((name, _)) = function_name(code, &self.rules.functions){
+                    self.current = Some(OpenFunction{ function: FunctionComplexity{ name, line: self.line, cyclomatic: 1, nesting: 0 }, base: self.blocks.len() });
+                }
+            }
+            if let Some(open) = self.current.as_mut(){
+                open.function.nesting = open.function.nesting.max(self.blocks.len().saturating_sub(open.base + 1) as u64);
+            }
+            self.logical = indent;
+        }
+        for c in code.chars(){
+            match c {
+                '(' | '[' | '{' => self.brackets += 1,
+                ')' | ']' | '}' => self.brackets = self.brackets.saturating_sub(1),
+                _ => {},
+            }
+        }
+        // 逻辑行以`:`结尾时打开一个代码块
+        if self.brackets == 0 && code.trim_end().ends_with(':'){
+            self.blocks.push(self.logical);
+        }
+        if let Some(open) = self.current.as_mut(){
+            open.function.cyclomatic += branch_positions(code, &self.rules.branches).len() as u64;
+        }
+    }
+
+    fn close(&mut self){
+        if let Some(open) = self.current.take(){
+            self.functions.push(open.function);
+        }
+    }
+
+    /// 没有结束的函数到文件末尾为止
+    pub(crate) fn finish(mut self)->Complexity{
+        self.close();
+        Complexity{ functions: self.functions }
+    }
+}
+
+fn is_ident(c: char)->bool{
+    c.is_alphanumeric() || c == '_'
+}
+
+/// 分支关键字与运算符出现的位置，关键字必须是完整的单词
+fn branch_positions(code: &str, branches: &[String])->Vec<usize>{
+    let mut positions = Vec::new();
+    for branch in branches.iter().filter(|b| !b.is_empty()){
+        let word = branch.chars().all(is_ident);
+        for (position, _) in code.match_indices(branch.as_str()){
+            let before = code[..position].chars().next_back();
+            let after = code[position + branch.len()..].chars().next();
+            if !word || !(before.is_some_and(is_ident) || after.is_some_and(is_ident)){
+                positions.push(position);
+            }
+        }
+    }
+    positions
+}
+
+/// 声明的头部（`{`之前或python中的一行）是函数时返回函数名及其位置
+fn function_name(header: &str, keywords: &[String])->Option<(String, usize)>{
+    if keywords.is_empty(){
+        return declaration_name(header);
+    }
+    let words = words(header);
+    let index = words.iter().position(|(_, word)| keywords.iter().any(|k| k == word))?;
+    let (start, keyword) = words[index];
+    // go中方法的接收者：func (s *Server) Serve(...)
+    let mut rest = header[start + keyword.len()..].trim_start();
+    if rest.starts_with('('){
+        rest = rest[group_len(rest)..].trim_start();
+    }
+    let position = header.len() - rest.len();
+    let name = rest.chars().take_while(|c| is_ident(*c)).collect::<String>();
+    match name.is_empty() {
+        true => Some(("<anonymous>".to_string(), start)),
+        false => Some((name, position)),
+    }
+}
+
+/// c风格的声明`修饰符 返回类型 名称(参数) throws ... {`，忽略注解
+fn declaration_name(header: &str)->Option<(String, usize)>{
+    let mut text = String::with_capacity(header.len());
+    let mut rest = header;
+    while let Some(c) = rest.chars().next(){
+        if c == '@'{
+            // 注解换成等长的空格，保持位置不变
+            let len = 1 + rest[1..].chars().take_while(|c| is_ident(*c) || *c == '.').map(char::len_utf8).sum::<usize>();
+            let len = len + if rest[len..].starts_with('(') { group_len(&rest[len..]) } else { 0 };
+            text.push_str(&" ".repeat(len));
+            rest = &rest[len..];
+            continue;
+        }
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    let open = text.find('(')?;
+    let before = &text[..open];
+    if before.contains(['=', '.']) || words(before).iter().any(|(_, word)| NOT_FUNCTIONS.contains(word)){
+        return None;
+    }
+    let name = before.trim_end();
+    let start = name.len() - name.chars().rev().take_while(|c| is_ident(*c)).map(char::len_utf8).sum::<usize>();
+    let name = &name[start..];
+    match name.chars().next() {
+        Some(first) if !first.is_ascii_digit() => Some((name.to_string(), start)),
+        _ => None,
+    }
+}
+
+/// 按单词拆分，返回每个单词的位置
+fn words(text: &str)->Vec<(usize, &str)>{
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))){
+        match (is_ident(c), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, &text[s..i]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    words
+}
+
+/// 以`(`开始的括号（包含嵌套的括号）的长度，没有关闭时为剩余的全部
+fn group_len(text: &str)->usize{
+    let mut depth = 0;
+    for (i, c) in text.char_indices(){
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0{
+                    return i + 1;
+                }
+            },
+            _ => {},
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::PathBuf;
+
+    use super::{complexity_summary, Complexity, ComplexityScanner, FunctionComplexity};
+    use crate::{breakdown::FileStats, count::{scan_reader, LanguageStats, LineCounter}, lang::Languages, origin::Origin};
+
+    fn scan(language: &str, content: &str)->Vec<(String, u64, u64, u64)>{
+        let languages = Languages::builtin();
+        let language = languages.find_tag(language).unwrap();
+        let mut counter = LineCounter::new(language).keep_code();
+        let mut scanner = ComplexityScanner::new(language.complexity.as_ref().unwrap());
+        for line in content.lines(){
+            counter.line(line.as_bytes());
+            scanner.line(counter.code());
+        }
+        scanner.finish().functions.into_iter().map(|f| (f.name, f.line, f.cyclomatic, f.nesting)).collect()
+    }
+
+    #[test]
+    fn test_rust(){
+        let content = r#"
+/// if 注释中的不算
+#[test]
+pub(crate) fn parse<T>(a: bool, b: bool)->Result<(), E>{
+    let s = "if while";
+    if a && b {
+        for x in 0..3 {
+            match x { 0 => {}, _ => {} }
+        }
+    }
+    let f = |x: u8| if x > 1 { 1 } else { 2 };
+}
+
+trait T {
+    fn declared(&self);
+    fn provided(&self){}
+}
+"#;
+        assert_eq!(vec![("parse".to_string(), 4, 7, 4), ("provided".to_string(), 16, 1, 0)], scan("rust", content));
+    }
+
+    #[test]
+    fn test_python(){
+        let content = r#"
+class Handler:
+    async def handle(self, request):
+        """if while 文档"""
+        if request.ok and request.body:
+            for part in request.body:
+                while part:
+                    part = part[1:]
+        elif request.retry or (request.count >
+                               3):
+            pass
+
+    def ok(self):
+        return [x for x in range(3) if x]
+def top():
+    def inner():
+        if True:
+            pass
+"#;
+        assert_eq!(vec![("handle".to_string(), 3, 7, 3), ("ok".to_string(), 13, 3, 0), ("top".to_string(), 15, 2, 2)], scan("python", content));
+    }
+
+    #[test]
+    fn test_go_and_java(){
+        let go = r#"
+func (s *Server) Serve(addr string) error {
+    for {
+        if err := s.accept(); err != nil || s.closed {
+            return err
+        }
+    }
+}
+func main() {
+    go func() { if true {} }()
+}
+"#;
+        assert_eq!(vec![("Serve".to_string(), 2, 4, 2), ("main".to_string(), 9, 2, 2)], scan("go", go));
+        let java = r#"
+public class App {
+    private static final int[] SIZES = {1, 2};
+
+    @Override
+    @SuppressWarnings("unchecked")
+    public <T> List<T> load(String path) throws IOException {
+        try {
+            if (path.isEmpty()) { return List.of(); }
+        } catch (IOException e) {
+            throw e;
+        }
+        return items.stream().filter(x -> { return x != null && x.ok(); }).toList();
+    }
+
+    App() { super(); }
+}
+"#;
+        assert_eq!(vec![("load".to_string(), 7, 4, 2), ("App".to_string(), 16, 1, 0)], scan("java", java));
+    }
+
+    /// 只有要求计算并且语言有`complexity`规则时才有结果
+    #[test]
+    fn test_scanned(){
+        let languages = Languages::builtin();
+        let scan = |tag: &str, content: &str, complexity: bool| scan_reader(content.as_bytes(), languages.find_tag(tag).unwrap(), Some(&languages), complexity).unwrap().complexity;
+        let main = "fn main(){\n    if true && false {}\n}\n";
+        assert_eq!(None, scan("rust", main, false));
+        assert_eq!(Some(vec![("main".to_string(), 1, 3, 1)]), scan("rust", main, true).map(|c| c.functions.into_iter().map(|f| (f.name, f.line, f.cyclomatic, f.nesting)).collect()));
+        assert_eq!(None, scan("markdown", "# 说明\n", true));
+    }
+
+    #[test]
+    fn test_summary(){
+        let function = |name: &str, cyclomatic: u64, nesting: u64| FunctionComplexity{ name: name.to_string(), line: 1, cyclomatic, nesting };
+        let file = |path: &str, language: &str, functions: Option<Vec<FunctionComplexity>>| FileStats{
+            path: PathBuf::from(path),
+            root: PathBuf::new(),
+            language: language.to_string(),
+            stats: LanguageStats{ files: 1, code: 10, comment: 0, blank: 0 },
+            complexity: functions.map(|functions| Complexity{ functions }),
+            ..Default::default()
+        };
+        let files = vec![
+            file("a.rs", "Rust", Some(vec![function("a", 3, 1), function("b", 10, 4)])),
+            file("b.rs", "Rust", Some(vec![])),
+            file("c.py", "Python", Some(vec![function("c", 10, 2)])),
+            file("d.md", "Markdown", None),
+            FileStats{ origin: Origin::Vendored, ..file("vendor/e.rs", "Rust", Some(vec![function("e", 20, 5)])) },
+            FileStats{ test: LanguageStats{ files: 1, code: 10, comment: 0, blank: 0 }, ..file("tests/f.rs", "Rust", Some(vec![function("f", 20, 5)])) },
+        ];
+        let summary = complexity_summary(&files, 2);
+        assert_eq!(vec!["Rust", "Python"], summary.languages.iter().map(|l| l.language.as_str()).collect::<Vec<_>>());
+        let rust = &summary.languages[0];
+        assert_eq!((2, 2, 13, 10, 4), (rust.files, rust.functions, rust.cyclomatic, rust.max_cyclomatic, rust.max_nesting));
+        assert_eq!((1.0, 6.5, 2.5), (rust.avg_functions, rust.avg_cyclomatic, rust.avg_nesting));
+        assert_eq!(vec!["b", "c"], summary.functions.iter().map(|f| f.function.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(vec![(PathBuf::from("a.rs"), 2, 13, 10, 4), (PathBuf::from("c.py"), 1, 10, 10, 2)],
+            summary.files.iter().map(|f| (f.path.clone(), f.functions, f.cyclomatic, f.max_cyclomatic, f.max_nesting)).collect::<Vec<_>>());
+        assert_eq!(4, files[0].complexity.as_ref().unwrap().max_nesting());
+        assert_eq!(13, files[0].complexity.as_ref().unwrap().cyclomatic());
+    }
+}
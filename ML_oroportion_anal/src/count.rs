@@ -0,0 +1,597 @@
+use std::{collections::BTreeMap, fmt, io::{self, Read}, ops::AddAssign, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use walkfile::{sniff, ContentType, TextEncoding, SNIFF_LEN};
+
+use crate::{complexity::{Complexity, ComplexityScanner}, embedded::{scan_notebook, Embedding, Fences}, lang::{Language, Languages}, origin::ContentScan, testcode::TestRegions};
+
+/// # LanguageStats
+/// * files: 文件数
+/// * code: 代码行，包含代码的行，代码后面跟着注释也算代码行
+/// * comment: 注释行，只包含注释的行
+/// * blank: 空行，只包含空白字符的行
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageStats{
+    pub files: u64,
+    pub code: u64,
+    pub comment: u64,
+    pub blank: u64,
+}
+
+impl LanguageStats {
+    /// 总行数
+    pub fn lines(&self)->u64{
+        self.code + self.comment + self.blank
+    }
+
+    /// 没有文件也没有行
+    pub fn is_empty(&self)->bool{
+        *self == LanguageStats::default()
+    }
+
+    /// 按分类记录一行
+    pub(crate) fn count(&mut self, kind: LineKind){
+        match kind {
+            LineKind::Code => self.code += 1,
+            LineKind::Comment => self.comment += 1,
+            LineKind::Blank => self.blank += 1,
+        }
+    }
+}
+
+impl AddAssign for LanguageStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.files += rhs.files;
+        self.code += rhs.code;
+        self.comment += rhs.comment;
+        self.blank += rhs.blank;
+    }
+}
+
+/// 单行的分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind{
+    Code,
+    Comment,
+    Blank,
+}
+
+/// # LineCounter
+/// 逐行统计代码、注释、空行，跨行的块注释与字符串状态保存在计数器中
+pub struct LineCounter<'a>{
+    language: &'a Language,
+    /// 当前所在的块注释及嵌套深度
+    block: Option<(usize, usize)>,
+    /// 当前所在的字符串
+    string: Option<usize>,
+    stats: LanguageStats,
+    /// 上一行去掉注释与字符串后的代码，只在调用`keep_code`后记录
+    code: Option<Vec<u8>>,
+}
+
+impl<'a> LineCounter<'a> {
+    pub fn new(language: &'a Language)->LineCounter<'a>{
+        LineCounter{
+            language,
+            block: None,
+            string: None,
+            stats: LanguageStats::default(),
+            code: None,
+        }
+    }
+
+    /// 记录每一行去掉注释与字符串后的代码，供`code`读取
+    pub(crate) fn keep_code(mut self)->LineCounter<'a>{
+        self.code = Some(Vec::new());
+        self
+    }
+
+    /// 上一行去掉注释与字符串后的代码，注释、字符串以及字符字面量替换为一个空格
+    pub(crate) fn code(&self)->&[u8]{
+        self.code.as_deref().unwrap_or_default()
+    }
+
+    /// 处理一行（不包含换行符），返回这一行的分类
+    pub fn line(&mut self, line: &[u8])->LineKind{
+        let language = self.language;
+        let mut has_code = false;
+        let mut has_comment = false;
+        let mut code = self.code.take();
+        if let Some(code) = code.as_mut(){
+            code.clear();
+        }
+        let mut i = 0;
+        while i < line.len(){
+            let rest = &line[i..];
+            let blank = rest[0].is_ascii_whitespace();
+            if let Some(index) = self.string{
+                let quote = &language.quotes[index];
+                has_code |= !blank;
+                if quote.escape && rest[0] == b'\\'{
+                    i += 2;
+                }else if rest.starts_with(quote.end.as_bytes()){
+                    self.string = None;
+                    i += quote.end.len();
+                }else{
+                    i += 1;
+                }
+                continue;
+            }
+            if let Some((index, depth)) = self.block{
+                let (start, end) = &language.block_comments[index];
+                has_comment |= !blank;
+                if language.nested && rest.starts_with(start.as_bytes()){
+                    self.block = Some((index, depth + 1));
+                    i += start.len();
+                }else if rest.starts_with(end.as_bytes()){
+                    self.block = if depth > 1 { Some((index, depth - 1)) } else { None };
+                    i += end.len();
+                }else{
+                    i += 1;
+                }
+                continue;
+            }
+            if blank{
+                code.iter_mut().for_each(|c| c.push(rest[0]));
+                i += 1;
+                continue;
+            }
+            // 块注释优先，lua中的`--[[`不能被当作行注释
+            if let Some(index) = language.block_comments.iter().position(|(start, _)| rest.starts_with(start.as_bytes())){
+                code.iter_mut().for_each(|c| c.push(b' '));
+                self.block = Some((index, 1));
+                has_comment = true;
+                i += language.block_comments[index].0.len();
+                continue;
+            }
+            if language.line_comments.iter().any(|c| rest.starts_with(c.as_bytes())){
+                has_comment = true;
+                break;
+            }
+            has_code = true;
+            if let Some(index) = language.quotes.iter().position(|q| rest.starts_with(q.start.as_bytes())){
+                code.iter_mut().for_each(|c| c.push(b' '));
+                self.string = Some(index);
+                i += language.quotes[index].start.len();
+                continue;
+            }
+            if language.char_literals && rest[0] == b'\''{
+                if let Some(len) = char_literal_len(rest){
+                    code.iter_mut().for_each(|c| c.push(b' '));
+                    i += len;
+                    continue;
+                }
+            }
+            code.iter_mut().for_each(|c| c.push(rest[0]));
+            i += 1;
+        }
+        self.code = code;
+        let kind = if has_code {
+            LineKind::Code
+        }else if has_comment {
+            LineKind::Comment
+        }else{
+            LineKind::Blank
+        };
+        self.stats.count(kind);
+        kind
+    }
+
+    pub fn stats(&self)->LanguageStats{
+        self.stats
+    }
+}
+
+/// `'a'`、`'\n'`、`'\u{4e2d}'`、`'中'`这样的字符字面量的长度，不是字符字面量（例如生命周期`'a`）时返回None
+fn char_literal_len(rest: &[u8])->Option<usize>{
+    match rest.get(1)? {
+        b'\\' => rest.iter().skip(3).take(10).position(|b| *b == b'\'').map(|p| p + 4),
+        first => {
+            let len = match first {
+                0x00..=0x7f => 1,
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            match rest.get(1 + len) {
+                Some(b'\'') => Some(len + 2),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// 跳过文件的原因
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkipReason{
+    /// 含有NUL字节或能识别魔数的二进制文件
+    Binary,
+    /// 无法打开或读取，保存错误信息
+    Unreadable(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Binary => write!(f, "二进制文件"),
+            SkipReason::Unreadable(e) => write!(f, "无法读取: {}", e),
+        }
+    }
+}
+
+/// 没有统计的文件及原因
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFile{
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// 每次从文件读取的字节数
+pub const BUFFER_LEN: usize = 64 * 1024;
+/// 单行最多保存的字节数，超出的部分不参与判断，保证超长的行也只占用有限的内存
+pub const MAX_LINE_LEN: usize = 1024 * 1024;
+
+/// 边读取边统计，内存占用与文件大小无关：
+/// 去掉BOM，UTF-16转为UTF-8，不是合法UTF-8的内容（例如Latin-1）按字节处理，
+/// 二进制文件返回`SkipReason::Binary`，读取失败返回`SkipReason::Unreadable`。
+/// 不拆分嵌入的代码，Markdown中的代码块按Markdown统计，notebook按原始的json统计
+pub fn count_reader<R: Read>(reader: R, language: &Language)->Result<LanguageStats, SkipReason>{
+    scan_reader(reader, language, None, false).map(|scanned| scanned.stats)
+}
+
+/// # Scanned
+/// 统计一个文件的完整结果
+/// * stats: 文件本身语言的行数，不包含嵌入的代码
+/// * embedded: 嵌入的代码按语言统计的行数
+/// * test: stats中`#[cfg(test)]`标记的部分，没有时为空
+/// * complexity: 函数的复杂度，没有要求计算或语言没有`complexity`规则时为None
+/// * scan: 用于判断生成代码的内容特征
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Scanned{
+    pub(crate) stats: LanguageStats,
+    pub(crate) embedded: BTreeMap<String, LanguageStats>,
+    pub(crate) test: LanguageStats,
+    pub(crate) complexity: Option<Complexity>,
+    pub(crate) scan: ContentScan,
+}
+
+/// 与`count_reader`相同，languages不为None时按语言的`embedded`拆分嵌入的代码，complexity为true时计算函数的复杂度
+pub(crate) fn scan_reader<R: Read>(reader: R, language: &Language, languages: Option<&Languages>, complexity: bool)->Result<Scanned, SkipReason>{
+    let fences = match (language.embedded, languages) {
+        (Some(Embedding::Notebook), Some(languages)) => return scan_notebook(reader, languages),
+        (Some(Embedding::Fences), Some(languages)) => Some(Fences::new(languages)),
+        _ => None,
+    };
+    let complexity = language.complexity.as_ref().filter(|_| complexity).map(ComplexityScanner::new);
+    split_reader(reader, LineSplitter::new(language, fences, complexity)).map(|lines| lines.finish().0)
+}
+
+/// 一行属于文件的哪一部分：文件本身的语言、`#[cfg(test)]`标记的测试代码或者嵌入的代码（语言名称）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinePart<'a>{
+    Source,
+    Test,
+    Embedded(&'a str),
+}
+
+/// # LineClass
+/// 一行的分类，用于blame把每一行归属到作者
+/// * line: 这一行开始之前`\n`的个数，即git按`\n`拆分时的行号（从0开始）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LineClass<'a>{
+    pub(crate) line: usize,
+    pub(crate) part: LinePart<'a>,
+    pub(crate) kind: LineKind,
+}
+
+/// 与`scan_reader`相同，同时返回每一行的分类；notebook不按行统计，由调用方跳过
+pub(crate) fn classify_reader<'a, R: Read>(reader: R, language: &'a Language, languages: &'a Languages)->Result<(Scanned, Vec<LineClass<'a>>), SkipReason>{
+    let fences = (language.embedded == Some(Embedding::Fences)).then(|| Fences::new(languages));
+    split_reader(reader, LineSplitter::new(language, fences, None).classify()).map(LineSplitter::finish)
+}
+
+/// 识别编码后把内容交给LineSplitter
+fn split_reader<'a, R: Read>(mut reader: R, mut lines: LineSplitter<'a>)->Result<LineSplitter<'a>, SkipReason>{
+    let unreadable = |e: io::Error| SkipReason::Unreadable(e.to_string());
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    reader.by_ref().take(SNIFF_LEN as u64).read_to_end(&mut head).map_err(unreadable)?;
+    let mut decoder = match sniff(&head) {
+        ContentType::Known(_) | ContentType::Binary => return Err(SkipReason::Binary),
+        ContentType::Text{ encoding: TextEncoding::Utf16Le, .. } => Decoder::Utf16{ from_bytes: u16::from_le_bytes, pending: Vec::new() },
+        ContentType::Text{ encoding: TextEncoding::Utf16Be, .. } => Decoder::Utf16{ from_bytes: u16::from_be_bytes, pending: Vec::new() },
+        _ => Decoder::Bytes,
+    };
+    let mut decoded = Vec::new();
+    let mut chunk = match decoder {
+        Decoder::Bytes => head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&head).to_vec(),
+        Decoder::Utf16{ .. } => head[2..].to_vec(),
+    };
+    let mut buf = vec![0; BUFFER_LEN];
+    loop {
+        decoded.clear();
+        // 开头之后出现的NUL同样说明是二进制文件
+        if !decoder.decode(&chunk, &mut decoded){
+            return Err(SkipReason::Binary);
+        }
+        lines.feed(&decoded);
+        let len = loop {
+            match reader.read(&mut buf) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                len => break len.map_err(unreadable)?,
+            }
+        };
+        if len == 0{
+            break;
+        }
+        chunk.clear();
+        chunk.extend_from_slice(&buf[..len]);
+    }
+    Ok(lines)
+}
+
+/// 把读取到的字节转换为按UTF-8处理的字节
+enum Decoder{
+    Bytes,
+    /// pending保存被缓冲区截断的半个字符
+    Utf16{ from_bytes: fn([u8; 2])->u16, pending: Vec<u8> },
+}
+
+impl Decoder {
+    /// 遇到NUL时返回false
+    fn decode(&mut self, chunk: &[u8], out: &mut Vec<u8>)->bool{
+        match self {
+            Decoder::Bytes => {
+                out.extend_from_slice(chunk);
+                !chunk.contains(&0)
+            },
+            Decoder::Utf16{ from_bytes, pending } => {
+                pending.extend_from_slice(chunk);
+                let mut units = pending.chunks_exact(2).map(|c| from_bytes([c[0], c[1]])).collect::<Vec<_>>();
+                let mut used = units.len() * 2;
+                // 末尾的高位代理需要和下一个缓冲区中的低位代理一起解码
+                if matches!(units.last(), Some(0xd800..=0xdbff)){
+                    units.pop();
+                    used -= 2;
+                }
+                for c in char::decode_utf16(units){
+                    let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                pending.drain(..used);
+                !out.contains(&0)
+            },
+        }
+    }
+}
+
+/// # LineSplitter
+/// 把任意切分的字节流拆成行交给`LineCounter`，支持`\n`、`\r\n`以及单独的`\r`，
+/// 被缓冲区截断的行以及`\r\n`会等到下一个缓冲区再处理
+struct LineSplitter<'a>{
+    counter: LineCounter<'a>,
+    scan: ContentScan,
+    fences: Option<Fences<'a>>,
+    tests: Option<(TestRegions, LanguageStats)>,
+    complexity: Option<ComplexityScanner<'a>>,
+    line: Vec<u8>,
+    /// 当前行截断前的长度
+    len: usize,
+    /// 上一个缓冲区以`\r`结尾，下一个字节是`\n`时需要跳过
+    after_cr: bool,
+    /// 已经处理的`\n`个数以及当前行开始时的个数
+    newlines: usize,
+    start: usize,
+    /// 记录每一行的分类，只有blame需要
+    classes: Option<Vec<LineClass<'a>>>,
+}
+
+impl<'a> LineSplitter<'a> {
+    fn new(language: &'a Language, fences: Option<Fences<'a>>, complexity: Option<ComplexityScanner<'a>>)->LineSplitter<'a>{
+        let tests = TestRegions::supports(language).then(|| (TestRegions::default(), LanguageStats::default()));
+        let counter = match tests.is_some() || complexity.is_some() {
+            true => LineCounter::new(language).keep_code(),
+            false => LineCounter::new(language),
+        };
+        LineSplitter{ counter, scan: ContentScan::default(), fences, tests, complexity, line: Vec::new(), len: 0, after_cr: false, newlines: 0, start: 0, classes: None }
+    }
+
+    /// 记录每一行的分类
+    fn classify(self)->LineSplitter<'a>{
+        LineSplitter{ classes: Some(Vec::new()), ..self }
+    }
+
+    fn feed(&mut self, mut bytes: &[u8]){
+        if self.after_cr && !bytes.is_empty(){
+            self.after_cr = false;
+            if let Some(rest) = bytes.strip_prefix(b"\n"){
+                bytes = rest;
+                self.newlines += 1;
+                self.start = self.newlines;
+            }
+        }
+        while let Some(end) = bytes.iter().position(|b| *b == b'\n' || *b == b'\r'){
+            self.push(&bytes[..end]);
+            self.end_line();
+            let skip = match &bytes[end..] {
+                [b'\r', b'\n', ..] => 2,
+                [b'\r'] => {
+                    self.after_cr = true;
+                    1
+                },
+                _ => 1,
+            };
+            self.newlines += usize::from(bytes[end + skip - 1] == b'\n');
+            self.start = self.newlines;
+            bytes = &bytes[end + skip..];
+        }
+        self.push(bytes);
+    }
+
+    fn end_line(&mut self){
+        let (part, kind) = match self.fences.as_mut().and_then(|f| f.classify(&self.line)) {
+            Some((name, kind)) => (LinePart::Embedded(name), kind),
+            None => {
+                let kind = self.counter.line(&self.line);
+                let mut part = LinePart::Source;
+                if let Some((regions, stats)) = self.tests.as_mut(){
+                    if regions.line(self.counter.code()){
+                        stats.count(kind);
+                        part = LinePart::Test;
+                    }
+                }
+                if let Some(complexity) = self.complexity.as_mut(){
+                    complexity.line(self.counter.code());
+                }
+                (part, kind)
+            },
+        };
+        if let Some(classes) = self.classes.as_mut(){
+            classes.push(LineClass{ line: self.start, part, kind });
+        }
+        self.scan.line(&self.line, self.len);
+        self.line.clear();
+        self.len = 0;
+    }
+
+    fn push(&mut self, bytes: &[u8]){
+        self.len += bytes.len();
+        let len = bytes.len().min(MAX_LINE_LEN - self.line.len());
+        self.line.extend_from_slice(&bytes[..len]);
+    }
+
+    /// 最后一行没有换行符时同样统计，files为1，没有记录每一行的分类时分类为空
+    fn finish(mut self)->(Scanned, Vec<LineClass<'a>>){
+        if !self.line.is_empty(){
+            self.end_line();
+        }
+        let embedded = self.fences.map(Fences::finish).unwrap_or_default();
+        let test = match self.tests {
+            Some((_, test)) if test.lines() > 0 => LanguageStats{ files: 1, ..test },
+            _ => LanguageStats::default(),
+        };
+        let complexity = self.complexity.map(ComplexityScanner::finish);
+        let scanned = Scanned{ stats: LanguageStats{ files: 1, ..self.counter.stats() }, embedded, test, complexity, scan: self.scan };
+        (scanned, self.classes.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use std::io::{self, Read};
+
+    use super::{classify_reader, count_reader, LanguageStats, LineKind, LinePart, SkipReason, MAX_LINE_LEN};
+    use crate::lang::{Language, Languages};
+
+    fn count_bytes(content: &[u8], language: &Language)->Result<LanguageStats, SkipReason>{
+        count_reader(content, language)
+    }
+
+    fn count_lines(content: &str, language: &Language)->LanguageStats{
+        count_bytes(content.as_bytes(), language).unwrap()
+    }
+
+    fn language(name: &str)->Language{
+        Languages::builtin().iter().find(|l| l.name == name).unwrap().clone()
+    }
+
+    fn stats(code: u64, comment: u64, blank: u64)->LanguageStats{
+        LanguageStats{ files: 1, code, comment, blank }
+    }
+
+    #[test]
+    fn test_rust(){
+        let content = r#"
+/// 文档注释
+fn main(){
+    /* 块注释
+       /* 嵌套 */
+       仍然是注释 */
+    let s = "// 不是注释 /*";
+    let c = '"'; // 行尾注释
+    let r = r"/* 也不是注释";
+    fn f<'a>(x: &'a str)->&'a str{ x }
+
+}
+"#;
+        assert_eq!(stats(6, 4, 2), count_lines(content, &language("Rust")));
+    }
+
+    #[test]
+    fn test_python(){
+        let content = "# 注释\nimport os\n\ns = '# 不是注释'\n\"\"\"\n多行字符串 # 也不是注释\n\"\"\"\n";
+        assert_eq!(stats(5, 1, 1), count_lines(content, &language("Python")));
+    }
+
+    #[test]
+    fn test_go(){
+        let content = "package main\n/* a\n*/ func main() {}\nvar s = `\n// raw\n`\n";
+        assert_eq!(stats(5, 1, 0), count_lines(content, &language("Go")));
+    }
+
+    #[test]
+    fn test_lua(){
+        let content = "--[[ 块注释\n]]\n-- 行注释\nlocal s = [[ -- 不是注释 ]]\n";
+        assert_eq!(stats(1, 3, 0), count_lines(content, &language("Lua")));
+    }
+
+    #[test]
+    fn test_bytes(){
+        let rust = language("Rust");
+        let stats = LanguageStats{ files: 1, code: 2, comment: 1, blank: 1 };
+        assert_eq!(Ok(stats), count_bytes(b"// caf\xe9\r\nfn main(){}\r\n\r\nlet s = \"\xe9\";", &rust));
+        assert_eq!(Ok(stats), count_bytes(b"\xef\xbb\xbf// c\nfn main(){}\n\nlet s = 1;\n", &rust));
+        let utf16 = "\u{feff}// c\nfn main(){}\n\nlet s = \"/*\";\n".encode_utf16().flat_map(|u| u.to_le_bytes()).collect::<Vec<_>>();
+        assert_eq!(Ok(stats), count_bytes(&utf16, &rust));
+        assert_eq!(Err(SkipReason::Binary), count_bytes(b"fn main(){}\x00\x01", &rust));
+        let mut late = b"fn main(){}\n".repeat(200);
+        late.push(0);
+        assert_eq!(Err(SkipReason::Binary), count_bytes(&late, &rust));
+        assert_eq!(Err(SkipReason::Binary), count_bytes(b"\x7fELF\x02\x01\x01", &rust));
+        assert_eq!(Ok(LanguageStats{ files: 1, ..Default::default() }), count_bytes(b"", &rust));
+    }
+
+    /// 每次只读取一个字节，检查被缓冲区截断的行、`\r\n`以及UTF-16字符
+    struct OneByte<'a>(&'a [u8]);
+
+    impl Read for OneByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty(){
+                return io::Result::Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            io::Result::Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_stream(){
+        let rust = language("Rust");
+        let content = "/* a\r\n b */\rfn main(){\r\n    let s = \"x\n/* y\";\n\r\n}\n".repeat(300);
+        let expected = LanguageStats{ files: 1, code: 1200, comment: 600, blank: 300 };
+        assert_eq!(Ok(expected), count_bytes(content.as_bytes(), &rust));
+        assert_eq!(Ok(expected), count_reader(OneByte(content.as_bytes()), &rust));
+        let utf16 = format!("\u{feff}{}// 😀\n", content).encode_utf16().flat_map(|u| u.to_be_bytes()).collect::<Vec<_>>();
+        let expected = LanguageStats{ comment: 601, ..expected };
+        assert_eq!(Ok(expected), count_reader(OneByte(&utf16), &rust));
+        assert_eq!(Ok(LanguageStats{ files: 1, code: 1, comment: 0, blank: 2 }), count_bytes(b"\r\r\nx", &rust));
+        let mut long = vec![b'x'; MAX_LINE_LEN * 3];
+        long.extend_from_slice(b"\n// c\n");
+        assert_eq!(Ok(LanguageStats{ files: 1, code: 1, comment: 1, blank: 0 }), count_bytes(&long, &rust));
+    }
+
+    #[test]
+    fn test_classify(){
+        let languages = Languages::builtin();
+        let rust = language("Rust");
+        // 单独的`\r`不是git的换行，之后的行仍属于git的同一行
+        let content = "\u{feff}fn main(){}\r\n// a\r\r\n#[cfg(test)]\nmod test{}\n";
+        let (scanned, classes) = classify_reader(OneByte(content.as_bytes()), &rust, &languages).unwrap();
+        assert_eq!(LanguageStats{ files: 1, code: 3, comment: 1, blank: 1 }, scanned.stats);
+        let expected = [(0, LinePart::Source, LineKind::Code), (1, LinePart::Source, LineKind::Comment), (1, LinePart::Source, LineKind::Blank), (2, LinePart::Test, LineKind::Code), (3, LinePart::Test, LineKind::Code)];
+        assert_eq!(expected.to_vec(), classes.iter().map(|c| (c.line, c.part, c.kind)).collect::<Vec<_>>());
+        let markdown = language("Markdown");
+        let (_, classes) = classify_reader(&b"# a\n```rust\nfn main(){}\n```\n"[..], &markdown, &languages).unwrap();
+        assert_eq!(vec![LinePart::Source, LinePart::Source, LinePart::Embedded("Rust"), LinePart::Source], classes.iter().map(|c| c.part).collect::<Vec<_>>());
+        assert!(matches!(classify_reader(&b"\x7fELF\x02\x01\x01"[..], &rust, &languages), Err(SkipReason::Binary)));
+    }
+}
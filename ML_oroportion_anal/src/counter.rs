@@ -0,0 +1,505 @@
+use std::{collections::{HashMap, HashSet}, fs::{self, File}, path::{Path, PathBuf}, sync::{mpsc, Arc, Mutex}, thread};
+use anyhow::{anyhow, bail, Ok, Result};
+use walkfile::{walk_roots_each, Glob, HiddenPolicy, WalkOptions, DEFAULT_IGNORE_FILES};
+
+use crate::{breakdown::FileStats, count::{scan_reader, Scanned, SkipReason, SkippedFile}, git::{ls_tree, read_blobs, show_prefix, TreeEntry}, lang::{read_sample, sample, Language, Languages}, origin::{Attributes, AttributesCache, Origin, OriginHint}, report::Report, testcode::test_stats};
+
+/// 统计规则的版本，规则变化（例如拆分测试代码）后增加，使旧的历史缓存失效
+const RULES_VERSION: u32 = 1;
+
+/// # Counter
+/// 统计代码行数，通过链式调用设置选项，默认与命令行工具相同：
+/// 使用内置的语言定义，跳过隐藏文件，遵守`.gitignore`、`.ignore`，统计生成的代码与第三方代码，不计算复杂度，线程数为CPU核数
+/// ```rust
+/// use std::path::PathBuf;
+/// use ml_oroportion_anal::Counter;
+/// let report = Counter::new().lang("rust").exclude("target/").count(&[PathBuf::from("./src")]).unwrap();
+/// assert!(report.languages().contains_key("Rust"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Counter{
+    languages: Languages,
+    langs: Vec<String>,
+    walk: WalkOptions,
+    generated: bool,
+    complexity: bool,
+    threads: usize,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Counter{
+            languages: Languages::builtin(),
+            langs: Vec::new(),
+            walk: WalkOptions{
+                hidden: HiddenPolicy::Exclude,
+                ignore_files: DEFAULT_IGNORE_FILES.iter().map(|f| f.to_string()).collect(),
+                ..Default::default()
+            },
+            generated: true,
+            complexity: false,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl Counter {
+    pub fn new()->Counter{
+        Counter::default()
+    }
+
+    /// 使用的语言定义表
+    pub fn languages(mut self, languages: Languages)->Counter{
+        self.languages = languages;
+        self
+    }
+
+    /// 只统计指定的语言，不区分大小写，可以多次调用
+    pub fn lang(mut self, name: &str)->Counter{
+        self.langs.push(name.to_string());
+        self
+    }
+
+    /// 隐藏文件的处理策略
+    pub fn hidden(mut self, hidden: HiddenPolicy)->Counter{
+        self.walk.hidden = hidden;
+        self
+    }
+
+    /// 排除匹配的文件或文件夹，gitignore风格的通配符，可以多次调用
+    pub fn exclude(mut self, pattern: &str)->Counter{
+        self.walk.excludes.push(Glob::new(pattern));
+        self
+    }
+
+    /// 是否遵守`.gitignore`、`.ignore`忽略文件
+    pub fn ignore_files(mut self, enabled: bool)->Counter{
+        self.walk.ignore_files = match enabled {
+            true => DEFAULT_IGNORE_FILES.iter().map(|f| f.to_string()).collect(),
+            false => Vec::new(),
+        };
+        self
+    }
+
+    /// 是否统计生成的代码与第三方代码，统计时按语言汇总计入`GENERATED_BUCKET`，不统计时直接丢弃
+    pub fn generated(mut self, include: bool)->Counter{
+        self.generated = include;
+        self
+    }
+
+    /// 是否计算函数的复杂度（函数数、嵌套层数、圈复杂度），只对有`complexity`规则的语言生效
+    pub fn complexity(mut self, enabled: bool)->Counter{
+        self.complexity = enabled;
+        self
+    }
+
+    /// 并发统计的线程数，至少为1
+    pub fn threads(mut self, threads: usize)->Counter{
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// 统计指定的文件夹和文件，直接指定的文件总是统计，不受忽略规则影响。
+    /// 遍历文件夹的同时把文件交给多个线程统计，每个线程单独累计，最后合并并按路径排序，结果与单线程统计完全相同
+    pub fn count(&self, paths: &[PathBuf])->Result<Report>{
+        let langs = self.resolve_langs()?;
+        for path in paths.iter(){
+            if !path.exists(){
+                bail!("指定的路径{}不存在", path.display());
+            }
+        }
+        let detect = |path: &Path| self.detect(path, &langs, || read_sample(path).unwrap_or_default());
+        let (dirs, files): (Vec<PathBuf>, Vec<PathBuf>) = paths.iter().cloned().partition(|p| p.is_dir());
+        // 直接指定的文件按规范化后的路径去重，遍历文件夹时不再重复统计
+        let mut explicit = HashSet::new();
+        let files = files.into_iter().filter(|f| explicit.insert(fs::canonicalize(f).unwrap_or_else(|_| f.clone()))).collect::<Vec<_>>();
+        // walk_roots返回规范化的根目录，输出时换回调用者的写法
+        let mut roots = HashMap::new();
+        for dir in dirs.iter(){
+            roots.entry(fs::canonicalize(dir)?).or_insert(display_root(dir));
+        }
+        let (sender, receiver) = mpsc::sync_channel::<(PathBuf, &Language, OriginHint, FileStats)>(self.threads * 64);
+        // 所有线程退出后receiver被释放，遍历线程的send会失败并停止
+        let receiver = Arc::new(Mutex::new(receiver));
+        thread::scope(|scope| {
+            let workers = (0..self.threads).map(|_| {
+                let receiver = Arc::clone(&receiver);
+                scope.spawn(move || {
+                    let mut report = Report::default();
+                    loop {
+                        let job = receiver.lock().map_err(|_| anyhow!("统计线程异常退出"))?.recv();
+                        let (path, language, hint, file) = match job {
+                            Err(_) => break,
+                            std::result::Result::Ok(job) => job,
+                        };
+                        match count_file(&path, language, &self.languages, self.complexity) {
+                            std::result::Result::Ok(scanned) => {
+                                let test = test_stats(file.relative_path(), &scanned);
+                                self.push(&mut report, FileStats{ stats: scanned.stats, origin: hint.finish(&scanned.scan), embedded: scanned.embedded, test, complexity: scanned.complexity, ..file });
+                            },
+                            Err(reason) => report.skipped.push(SkippedFile{ path: file.path, reason }),
+                        }
+                    }
+                    Ok(report)
+                })
+            }).collect::<Vec<_>>();
+            drop(receiver);
+            let walked = (|| {
+                let mut attributes = AttributesCache::default();
+                // 来源按相对于仓库根目录的路径判断，不在git仓库中时按输出的路径判断
+                let mut send = |path: &Path, walk_root: &Path, root: &Path, relative: &Path| {
+                    if let Some(language) = detect(path){
+                        let (repo_relative, linguist) = attributes.get(walk_root, relative);
+                        let hint = OriginHint::new(&repo_relative.unwrap_or_else(|| root.join(relative)), linguist);
+                        let file = FileStats{ path: root.join(relative), root: root.to_path_buf(), language: language.name.clone(), ..Default::default() };
+                        if sender.send((path.to_path_buf(), language, hint, file)).is_err(){
+                            bail!("统计线程异常退出");
+                        }
+                    }
+                    Ok(())
+                };
+                for file in files.iter(){
+                    let parent = file.parent().unwrap_or(Path::new(""));
+                    let walk_root = fs::canonicalize(file)?.parent().map(Path::to_path_buf).unwrap_or_default();
+                    send(file, &walk_root, parent, Path::new(file.file_name().unwrap_or_default()))?;
+                }
+                // 每读取完一个文件夹就把其中的文件交给统计线程，遍历与统计同时进行
+                walk_roots_each(&dirs, &self.walk, |entry| {
+                    let root = roots.get(&entry.walk_root).unwrap_or(&entry.walk_root);
+                    for (path, relative) in entry.file_paths().zip(entry.relative_file_paths()){
+                        if explicit.is_empty() || fs::canonicalize(&path).map_or(true, |p| !explicit.contains(&p)){
+                            send(&path, &entry.walk_root, root, &relative)?;
+                        }
+                    }
+                    Ok(())
+                })
+            })();
+            drop(sender);
+            let mut report = Report::default();
+            for worker in workers{
+                let part = worker.join().map_err(|_| anyhow!("统计线程异常退出"))??;
+                report.files.extend(part.files);
+                report.skipped.extend(part.skipped);
+            }
+            walked?;
+            report.files.sort_by(|a, b| a.path.cmp(&b.path));
+            report.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(report)
+        })
+    }
+}
+
+impl Counter {
+    /// 统计本地git仓库中某个版本（分支、标签或提交）跟踪的文件，不需要检出，
+    /// repo是仓库的子文件夹时只统计其中的文件，文件内容通过`git cat-file`读取，同样遵守隐藏文件与排除规则
+    pub fn count_revision(&self, repo: &Path, rev: &str)->Result<Report>{
+        let root = display_root(repo);
+        let files = self.tracked_files(repo, rev)?;
+        let oids = files.iter().map(|(entry, _, _)| entry.oid.clone()).collect::<Vec<_>>();
+        let mut report = Report::default();
+        read_blobs(repo, &oids, |index, blob| {
+            let (entry, language, hint) = &files[index];
+            let path = root.join(&entry.path);
+            match scan_reader(blob, language, Some(&self.languages), self.complexity) {
+                std::result::Result::Ok(scanned) => {
+                    let origin = hint.finish(&scanned.scan);
+                    let test = test_stats(&entry.path, &scanned);
+                    let file = FileStats{ path, root: root.clone(), language: language.name.clone(), stats: scanned.stats, origin, embedded: scanned.embedded, test, complexity: scanned.complexity };
+                    self.push(&mut report, file);
+                },
+                Err(reason) => report.skipped.push(SkippedFile{ path, reason }),
+            }
+            Ok(())
+        })?;
+        report.files.sort_by(|a, b| a.path.cmp(&b.path));
+        report.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(report)
+    }
+
+    /// 某个版本中repo下需要统计的文件（路径相对于repo）、语言以及根据路径与`.gitattributes`对来源的判断
+    pub(crate) fn tracked_files(&self, repo: &Path, rev: &str)->Result<Vec<(TreeEntry, &Language, OriginHint)>>{
+        let langs = self.resolve_langs()?;
+        let prefix = show_prefix(repo)?;
+        let entries = ls_tree(repo, rev, &prefix)?;
+        // 先读取所有`.gitattributes`，浅的在前，深的规则优先
+        let mut gitattributes = entries.iter().filter(|e| e.path.file_name().is_some_and(|n| n == ".gitattributes")).collect::<Vec<_>>();
+        gitattributes.sort_by_key(|e| e.path.components().count());
+        let mut attributes = Attributes::default();
+        if !gitattributes.is_empty(){
+            read_blobs(repo, &gitattributes.iter().map(|e| e.oid.clone()).collect::<Vec<_>>(), |index, blob| {
+                let mut content = Vec::new();
+                blob.read_to_end(&mut content)?;
+                attributes.add(&String::from_utf8_lossy(&content), gitattributes[index].path.parent().unwrap_or(Path::new("")));
+                Ok(())
+            })?;
+        }
+        // 上层文件夹中的`.gitattributes`只用于判断来源，路径换成相对于repo的路径，来源仍然按相对于仓库根目录的路径判断
+        let entries = entries.into_iter().filter_map(|e| {
+            let path = e.path.strip_prefix(&prefix).ok()?.to_path_buf();
+            Some((TreeEntry{ oid: e.oid, path }, e.path))
+        }).filter(|(e, _)| self.allows_tracked(&e.path)).collect::<Vec<_>>();
+        // 文件名不能确定语言的文件需要读取内容
+        let ambiguous = entries.iter().enumerate().filter(|(_, (e, _))| self.languages.needs_content(&e.path)).map(|(i, _)| i).collect::<Vec<_>>();
+        let mut samples = HashMap::new();
+        if !ambiguous.is_empty(){
+            read_blobs(repo, &ambiguous.iter().map(|i| entries[*i].0.oid.clone()).collect::<Vec<_>>(), |index, blob| {
+                samples.insert(ambiguous[index], sample(blob)?);
+                Ok(())
+            })?;
+        }
+        let mut files = Vec::new();
+        for (index, (entry, full)) in entries.into_iter().enumerate(){
+            if let Some(language) = self.detect(&entry.path, &langs, || samples.remove(&index).unwrap_or_default()){
+                let hint = OriginHint::new(&full, attributes.get(&full));
+                files.push((entry, language, hint));
+            }
+        }
+        Ok(files)
+    }
+
+    /// 是否保留该来源的文件
+    pub(crate) fn keeps(&self, origin: Origin)->bool{
+        self.generated || origin.is_source()
+    }
+
+    /// 添加统计结果，不统计生成的代码时丢弃
+    fn push(&self, report: &mut Report, file: FileStats){
+        if self.keeps(file.origin){
+            report.files.push(file);
+        }
+    }
+
+    /// 使用的语言定义表
+    pub(crate) fn language_defs(&self)->&Languages{
+        &self.languages
+    }
+
+    /// 并发统计的线程数
+    pub(crate) fn thread_count(&self)->usize{
+        self.threads
+    }
+
+    /// 影响统计结果的选项的指纹，用于判断缓存是否有效，统计规则变化时同时修改`RULES_VERSION`。
+    /// 对选项序列化后的json计算FNV-1a哈希，不同的rust版本、不同的机器上结果相同
+    pub(crate) fn fingerprint(&self)->String{
+        let hidden = match &self.walk.hidden {
+            HiddenPolicy::Include => serde_json::json!("include"),
+            HiddenPolicy::Exclude => serde_json::json!("exclude"),
+            HiddenPolicy::ExcludeExcept(names) => serde_json::json!({ "exclude_except": names }),
+        };
+        let options = serde_json::json!({
+            "rules": RULES_VERSION,
+            "languages": self.languages.iter().collect::<Vec<_>>(),
+            "langs": self.langs,
+            "hidden": hidden,
+            "excludes": self.walk.excludes.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            "ignore_files": self.walk.ignore_files,
+            "generated": self.generated,
+        });
+        format!("{:016x}", fnv1a(options.to_string().as_bytes()))
+    }
+
+    /// 把`lang`指定的名称换成语言定义中的名称
+    fn resolve_langs(&self)->Result<Vec<String>>{
+        let mut langs = Vec::new();
+        for lang in self.langs.iter(){
+            match self.languages.iter().find(|l| l.name.eq_ignore_ascii_case(lang)) {
+                None => bail!("未知的语言{}", lang),
+                Some(language) => langs.push(language.name.clone()),
+            }
+        }
+        Ok(langs)
+    }
+
+    /// 判断文件的语言，不在langs中时返回None；文件名不能确定语言时才调用read读取内容
+    fn detect(&self, path: &Path, langs: &[String], read: impl FnOnce()->Vec<u8>)->Option<&Language>{
+        let language = match self.languages.needs_content(path) {
+            true => self.languages.detect_content(path, &read())?,
+            false => self.languages.detect(path)?,
+        };
+        if !langs.is_empty() && !langs.contains(&language.name){
+            return None;
+        }
+        Some(language)
+    }
+
+    /// 仓库中跟踪的文件使用与遍历文件夹时相同的隐藏文件与排除规则
+    fn allows_tracked(&self, path: &Path)->bool{
+        let parts = path.iter().collect::<Vec<_>>();
+        let mut prefix = PathBuf::new();
+        for (index, part) in parts.iter().enumerate(){
+            prefix.push(part);
+            let is_dir = index + 1 < parts.len();
+            if !self.walk.hidden.allows(&part.to_string_lossy()) || self.walk.excludes.iter().any(|e| e.matches(&prefix, is_dir)){
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 64位FNV-1a哈希
+fn fnv1a(bytes: &[u8])->u64{
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// 输出时使用的根目录，当前目录写作空路径
+fn display_root(path: &Path)->PathBuf{
+    match path == Path::new(".") {
+        true => PathBuf::new(),
+        false => path.to_path_buf(),
+    }
+}
+
+/// 统计一个文件，读取失败或是二进制文件时返回跳过的原因
+fn count_file(path: &Path, language: &Language, languages: &Languages, complexity: bool)->std::result::Result<Scanned, SkipReason>{
+    let file = File::open(path).map_err(|e| SkipReason::Unreadable(e.to_string()))?;
+    scan_reader(file, language, Some(languages), complexity)
+}
+
+#[cfg(test)]
+mod test{
+    use std::fs;
+
+    use walkfile::HiddenPolicy;
+
+    use super::{fnv1a, Counter};
+    use crate::{count::SkipReason, git::test::{init_repo, run_git}, lang::Languages, test::TempDir, origin::{Origin, GENERATED_BUCKET}, output::{render, OutputFormat, SortKey}};
+
+    #[test]
+    fn test_threads(){
+        let dir = TempDir::new("threads");
+        for i in 0..40{
+            let sub = dir.join(format!("m{}", i % 7));
+            fs::create_dir_all(&sub).unwrap();
+            fs::write(sub.join(format!("f{}.rs", i)), "// c\nfn main(){}\n\n".repeat(i + 1)).unwrap();
+            fs::write(sub.join(format!("f{}.py", i)), "# c\nprint(1)\n".repeat(i)).unwrap();
+        }
+        fs::write(dir.join("latin1.rs"), b"// caf\xe9\nfn main(){}\n").unwrap();
+        fs::write(dir.join("image.rs"), b"\x89PNG\r\n\x1a\n\x00").unwrap();
+        let paths = [dir.clone()];
+        let single = Counter::new().threads(1).count(&paths).unwrap();
+        let parallel = Counter::new().threads(8).count(&paths).unwrap();
+        let python = Counter::new().lang("PYTHON").count(&paths).unwrap();
+        let unknown = Counter::new().lang("Cobol2").count(&paths);
+        // 直接指定的文件同时在遍历的文件夹中时只统计一次
+        let overlapping = Counter::new().count(&[dir.join("latin1.rs"), dir.clone(), dir.join("m0/../latin1.rs")]).unwrap();
+        assert_eq!(single, parallel);
+        assert_eq!(single.languages(), overlapping.languages());
+        assert_eq!(vec![(dir.join("image.rs"), SkipReason::Binary)], single.skipped.iter().map(|f| (f.path.clone(), f.reason.clone())).collect::<Vec<_>>());
+        assert!(single.files[0].path.starts_with(&dir));
+        let (single, parallel) = (single.languages(), parallel.languages());
+        assert_eq!(821, single["Rust"].code);
+        assert_eq!(40, single["Python"].files);
+        assert_eq!(vec!["Python"], python.languages().keys().collect::<Vec<_>>());
+        assert!(unknown.is_err());
+        for format in [OutputFormat::Json, OutputFormat::Table]{
+            assert_eq!(render(&single, format, SortKey::Code).unwrap(), render(&parallel, format, SortKey::Code).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fingerprint(){
+        assert_eq!(0xcbf29ce484222325, fnv1a(b""));
+        assert_eq!(0xaf63dc4c8601ec8c, fnv1a(b"a"));
+        let fingerprint = Counter::new().fingerprint();
+        assert_eq!(fingerprint, Counter::new().fingerprint());
+        assert_ne!(fingerprint, Counter::new().lang("Rust").fingerprint());
+        assert_ne!(fingerprint, Counter::new().exclude("vendor/").fingerprint());
+        assert_ne!(Counter::new().hidden(HiddenPolicy::Exclude).fingerprint(), Counter::new().hidden(HiddenPolicy::ExcludeExcept(Vec::new())).fingerprint());
+    }
+
+    #[test]
+    fn test_revision(){
+        let repo = init_repo("revision");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::create_dir_all(repo.join(".github")).unwrap();
+        fs::write(repo.join("src/main.rs"), "// v1\nfn main(){}\n").unwrap();
+        fs::write(repo.join("build.py"), "print(1)\n").unwrap();
+        fs::write(repo.join(".github/ci.py"), "print(2)\n").unwrap();
+        fs::write(repo.join("logo.rs"), b"\x89PNG\r\n\x1a\n\x00").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "v1"]);
+        run_git(&repo, &["tag", "v1.2"]);
+        fs::write(repo.join("src/main.rs"), "fn main(){}\nfn other(){}\nfn third(){}\n").unwrap();
+        run_git(&repo, &["commit", "-q", "-am", "v2"]);
+        fs::write(repo.join("untracked.rs"), "fn x(){}\n").unwrap();
+        let counter = Counter::new();
+        let v1 = counter.count_revision(&repo, "v1.2").unwrap();
+        let head = counter.clone().exclude("*.py").count_revision(&repo, "HEAD").unwrap();
+        let missing = counter.count_revision(&repo, "v9");
+        let src = counter.count_revision(&repo.join("src"), "HEAD").unwrap();
+        assert_eq!(vec![repo.join("build.py"), repo.join("src/main.rs")], v1.files.iter().map(|f| f.path.clone()).collect::<Vec<_>>());
+        assert_eq!(vec![repo.join("logo.rs")], v1.skipped.iter().map(|f| f.path.clone()).collect::<Vec<_>>());
+        assert_eq!((1, 1), (v1.languages()["Rust"].code, v1.languages()["Rust"].comment));
+        assert_eq!(3, head.languages()["Rust"].code);
+        assert!(!head.languages().contains_key("Python"));
+        assert!(missing.is_err());
+        assert_eq!(vec![(repo.join("src"), repo.join("src/main.rs"))], src.files.iter().map(|f| (f.root.clone(), f.path.clone())).collect::<Vec<_>>());
+    }
+
+    /// 遍历文件夹与统计git版本的结果一致：来源、根据内容判断的语言、测试代码以及复杂度
+    #[test]
+    fn test_parity(){
+        let repo = init_repo("parity");
+        for dir in ["src", "tests", "vendor", "api", "gen", "bin"]{
+            fs::create_dir_all(repo.join(dir)).unwrap();
+        }
+        fs::write(repo.join(".gitattributes"), "gen/** linguist-generated\nvendor/keep.rs -linguist-vendored\n").unwrap();
+        fs::write(repo.join("src/lib.rs"), "pub fn add(a: u8, b: u8)->u8{ if a > b { a } else { b } }\n\n#[cfg(test)]\nmod test{\n    // 测试\n    #[test]\n    fn test_add(){}\n}\n").unwrap();
+        fs::write(repo.join("tests/cli.rs"), "#[test]\nfn cli(){}\n").unwrap();
+        fs::write(repo.join("vendor/x.rs"), "fn x(){}\n").unwrap();
+        fs::write(repo.join("vendor/keep.rs"), "fn keep(){}\n").unwrap();
+        fs::write(repo.join("api/user.rs"), "// @generated by protoc\nstruct User;\n").unwrap();
+        fs::write(repo.join("gen/a.rs"), "fn a(){}\n").unwrap();
+        fs::write(repo.join("yarn.lock"), "# yarn lockfile v1\n").unwrap();
+        fs::write(repo.join("bin/deploy"), "#!/usr/bin/env python3\nimport sys\n\nprint(sys.argv)\n").unwrap();
+        fs::write(repo.join("bin/README"), "说明\n").unwrap();
+        fs::write(repo.join("api.h"), "namespace api {\nclass Client;\n}\n").unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "v1"]);
+        let counter = Counter::new().complexity(true);
+        let local = counter.count(std::slice::from_ref(&*repo)).unwrap();
+        let head = counter.count_revision(&repo, "HEAD").unwrap();
+        assert_eq!(local.files, head.files);
+        assert_eq!(local.languages(), head.languages());
+        let origins = local.files.iter().map(|f| (f.path.strip_prefix(&repo).unwrap().to_str().unwrap(), f.language.as_str(), f.origin)).collect::<Vec<_>>();
+        assert_eq!(vec![
+            ("api/user.rs", "Rust", Origin::Generated), ("api.h", "C++", Origin::Source), ("bin/deploy", "Python", Origin::Source),
+            ("gen/a.rs", "Rust", Origin::Generated), ("src/lib.rs", "Rust", Origin::Source), ("tests/cli.rs", "Rust", Origin::Source),
+            ("vendor/keep.rs", "Rust", Origin::Source), ("vendor/x.rs", "Rust", Origin::Vendored), ("yarn.lock", "YAML", Origin::Generated),
+        ], origins);
+        let languages = local.languages();
+        assert_eq!((2, 2, 7, 4), (languages["Rust"].files, languages["Rust"].code, languages["Rust (test)"].code, languages[GENERATED_BUCKET].files));
+        assert_eq!(Some(vec![("add", 2), ("test_add", 1)]), local.files[4].complexity.as_ref().map(|c| c.functions.iter().map(|f| (f.name.as_str(), f.cyclomatic)).collect::<Vec<_>>()));
+        assert_eq!(local.languages(), serde_json::from_str::<crate::report::Report>(&serde_json::to_string(&local).unwrap()).unwrap().languages());
+        // 统计子文件夹或直接指定文件时同样使用仓库根目录的`.gitattributes`，按相对于仓库根目录的路径判断
+        let origins = |paths: &[std::path::PathBuf]| counter.count(paths).unwrap().files.iter().map(|f| f.origin).collect::<Vec<_>>();
+        assert_eq!(vec![Origin::Source, Origin::Vendored], origins(&[repo.join("vendor")]));
+        assert_eq!(vec![Origin::Vendored], origins(&[repo.join("vendor/x.rs")]));
+        assert_eq!(counter.count(&[repo.join("vendor")]).unwrap().files, counter.count_revision(&repo.join("vendor"), "HEAD").unwrap().files);
+        let python = Counter::new().lang("python").count_revision(&repo, "HEAD").unwrap();
+        assert_eq!(vec![repo.join("bin/deploy")], python.files.iter().map(|f| f.path.clone()).collect::<Vec<_>>());
+        let excluded = Counter::new().generated(false).count_revision(&repo, "HEAD").unwrap();
+        assert!(excluded.files.iter().all(|f| f.origin.is_source() && f.complexity.is_none()));
+    }
+
+    #[test]
+    fn test_embedded(){
+        let dir = TempDir::new("embedded");
+        fs::write(dir.join("README.md"), "# 示例\n```rust\nfn main(){}\n```\n").unwrap();
+        fs::write(dir.join("analysis.ipynb"), r#"{"cells": [{"cell_type": "code", "source": ["import os\n", "print(os.name)"]}], "metadata": {}}"#).unwrap();
+        fs::create_dir_all(dir.join("tests")).unwrap();
+        fs::write(dir.join("tests/check.ipynb"), r#"{"cells": [{"cell_type": "code", "source": "assert True\n"}], "metadata": {"language_info": {"name": "python"}}}"#).unwrap();
+        let report = Counter::new().count(std::slice::from_ref(&*dir)).unwrap();
+        let plain = crate::count::count_reader(fs::File::open(dir.join("README.md")).unwrap(), Languages::builtin().find_tag("markdown").unwrap()).unwrap();
+        let languages = report.languages();
+        assert_eq!((1, 3), (languages["Markdown"].files, languages["Markdown"].lines()));
+        assert_eq!((1, 1), (languages["Rust (embedded)"].files, languages["Rust (embedded)"].code));
+        assert_eq!(2, languages["Python (embedded)"].code);
+        assert_eq!((1, 1), (languages["Python (test)"].files, languages["Python (test)"].code));
+        assert_eq!((1, 0), (languages["Jupyter Notebook"].files, languages["Jupyter Notebook"].lines()));
+        assert_eq!(7, report.total().lines());
+        assert_eq!(4, plain.lines());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{collections::{BTreeMap, BTreeSet}, path::{Path, PathBuf}};
+use serde::Serialize;
+
+use crate::{count::LanguageStats, output::percent, report::Report};
+
+/// # LanguageDelta
+/// 一种语言（或合计）的变化
+/// * old、new: 变化前后的统计
+/// * lines、code、comment、blank: 总行数、代码、注释、空行的变化
+/// * old_percent、new_percent: 变化前后总行数的占比
+/// * percent: 占比变化的百分点
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageDelta{
+    pub language: String,
+    pub old: LanguageStats,
+    pub new: LanguageStats,
+    pub lines: i64,
+    pub code: i64,
+    pub comment: i64,
+    pub blank: i64,
+    pub old_percent: f64,
+    pub new_percent: f64,
+    pub percent: f64,
+}
+
+/// 文件的变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChange{
+    Added,
+    Removed,
+    Changed,
+}
+
+/// # FileDelta
+/// 新增、删除或者语言、代码、注释、空行任意一项发生变化的文件
+/// * path: 相对于根目录的路径，统计了多个根目录时为包含根目录的路径
+/// * language: 文件的语言，修改的文件为变化后的语言
+/// * old、new: 变化前后的统计（包含嵌入的代码），新增、删除的文件另一侧为空
+/// * lines、code、comment、blank: 总行数、代码、注释、空行的变化
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileDelta{
+    pub path: PathBuf,
+    pub language: String,
+    pub change: FileChange,
+    pub old: LanguageStats,
+    pub new: LanguageStats,
+    pub lines: i64,
+    pub code: i64,
+    pub comment: i64,
+    pub blank: i64,
+}
+
+/// 从old变为new的变化量
+fn change(old: u64, new: u64)->i64{
+    new as i64 - old as i64
+}
+
+/// # Diff
+/// 两次统计结果的比较
+/// * languages: 各语言的变化，按总行数变化的绝对值从大到小排列
+/// * total: 合计的变化
+/// * files: 新增、删除、修改的文件，按路径排序
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diff{
+    pub languages: Vec<LanguageDelta>,
+    pub total: LanguageDelta,
+    pub files: Vec<FileDelta>,
+}
+
+/// 比较两次统计结果，文件按相对于各自根目录的路径对应，因此可以比较两个不同的目录；
+/// 任意一次统计了多个根目录时，不同根目录下的相对路径可能相同，此时按包含根目录的路径对应
+pub fn diff(old: &Report, new: &Report)->Diff{
+    let (old_languages, new_languages) = (old.languages(), new.languages());
+    let (old_total, new_total) = (old.total(), new.total());
+    let delta = |language: &str, old: LanguageStats, new: LanguageStats| {
+        let old_percent = percent(old.lines(), old_total.lines());
+        let new_percent = percent(new.lines(), new_total.lines());
+        LanguageDelta{
+            language: language.to_string(),
+            old,
+            new,
+            lines: change(old.lines(), new.lines()),
+            code: change(old.code, new.code),
+            comment: change(old.comment, new.comment),
+            blank: change(old.blank, new.blank),
+            old_percent,
+            new_percent,
+            percent: ((new_percent - old_percent) * 100.0).round() / 100.0,
+        }
+    };
+    let names = old_languages.keys().chain(new_languages.keys()).collect::<BTreeSet<_>>();
+    let mut languages = names.into_iter().map(|name| {
+        let stats = |languages: &std::collections::HashMap<String, LanguageStats>| languages.get(name).copied().unwrap_or_default();
+        delta(name, stats(&old_languages), stats(&new_languages))
+    }).filter(|d| d.old != d.new).collect::<Vec<_>>();
+    languages.sort_by(|a, b| b.lines.abs().cmp(&a.lines.abs()).then_with(|| a.language.cmp(&b.language)));
+    let total = delta("Total", old_total, new_total);
+
+    let multi_root = [old, new].iter().any(|r| r.files.iter().map(|f| &f.root).collect::<BTreeSet<_>>().len() > 1);
+    let relative = |report: &Report| report.files.iter().map(|f| {
+        let path = if multi_root { f.path.clone() } else { f.relative_path().to_path_buf() };
+        (path, (f.language.clone(), f.total()))
+    }).collect::<BTreeMap<PathBuf, (String, LanguageStats)>>();
+    let (old_files, new_files) = (relative(old), relative(new));
+    let paths = old_files.keys().chain(new_files.keys()).collect::<BTreeSet<&PathBuf>>();
+    let files = paths.into_iter().filter_map(|path| {
+        let file = |language: &str, kind, old: LanguageStats, new: LanguageStats| FileDelta{
+            path: Path::to_path_buf(path),
+            language: language.to_string(),
+            change: kind,
+            old,
+            new,
+            lines: change(old.lines(), new.lines()),
+            code: change(old.code, new.code),
+            comment: change(old.comment, new.comment),
+            blank: change(old.blank, new.blank),
+        };
+        match (old_files.get(path), new_files.get(path)) {
+            (None, Some((language, stats))) => Some(file(language, FileChange::Added, LanguageStats::default(), *stats)),
+            (Some((language, stats)), None) => Some(file(language, FileChange::Removed, *stats, LanguageStats::default())),
+            (Some(old), Some(new)) if old != new => Some(file(&new.0, FileChange::Changed, old.1, new.1)),
+            _ => None,
+        }
+    }).collect();
+    Diff{ languages, total, files }
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::PathBuf;
+
+    use super::{diff, FileChange};
+    use crate::{breakdown::FileStats, count::LanguageStats, report::Report};
+
+    fn report(root: &str, files: &[(&str, &str, u64, u64)])->Report{
+        let files = files.iter().map(|(path, language, code, comment)| FileStats{
+            path: PathBuf::from(root).join(path),
+            root: PathBuf::from(root),
+            language: language.to_string(),
+            stats: LanguageStats{ files: 1, code: *code, comment: *comment, blank: 0 },
+            ..Default::default()
+        }).collect();
+        Report{ files, skipped: Vec::new() }
+    }
+
+    #[test]
+    fn test_diff(){
+        let old = report("v1", &[("main.rs", "Rust", 60, 0), ("build.py", "Python", 40, 0), ("lib.rs", "Rust", 10, 0), ("mod.rs", "Rust", 5, 1)]);
+        let new = report("v2", &[("main.rs", "Rust", 90, 0), ("lib.rs", "Rust", 10, 0), ("Cargo.toml", "TOML", 20, 0), ("mod.rs", "Rust", 6, 0)]);
+        let diff = diff(&old, &new);
+        let languages = diff.languages.iter().map(|d| (d.language.as_str(), d.lines)).collect::<Vec<_>>();
+        assert_eq!(vec![("Python", -40), ("Rust", 30), ("TOML", 20)], languages);
+        assert_eq!((65.52, 84.13), (diff.languages[1].old_percent, diff.languages[1].new_percent));
+        assert_eq!(18.61, diff.languages[1].percent);
+        assert_eq!((31, -1), (diff.languages[1].code, diff.languages[1].comment));
+        assert_eq!(10, diff.total.lines);
+        // 总行数不变但代码、注释发生变化的文件也算修改
+        let files = diff.files.iter().map(|f| (f.path.to_str().unwrap(), f.change, f.lines, f.code, f.comment)).collect::<Vec<_>>();
+        assert_eq!(vec![
+            ("Cargo.toml", FileChange::Added, 20, 20, 0), ("build.py", FileChange::Removed, -40, -40, 0),
+            ("main.rs", FileChange::Changed, 30, 30, 0), ("mod.rs", FileChange::Changed, 0, 1, -1),
+        ], files);
+        assert_eq!((6, 6), (diff.files[3].old.lines(), diff.files[3].new.lines()));
+    }
+
+    #[test]
+    fn test_multi_root(){
+        let mut old = report("app", &[("src/main.rs", "Rust", 10, 0)]);
+        old.files.extend(report("lib", &[("src/main.rs", "Rust", 20, 0)]).files);
+        let mut new = report("app", &[("src/main.rs", "Rust", 10, 0)]);
+        new.files.extend(report("lib", &[("src/main.rs", "Rust", 25, 0)]).files);
+        let files = diff(&old, &new).files.iter().map(|f| (f.path.clone(), f.change, f.code)).collect::<Vec<_>>();
+        assert_eq!(vec![(PathBuf::from("lib/src/main.rs"), FileChange::Changed, 5)], files);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, io::{BufReader, Read}};
+use serde::{Deserialize, Serialize};
+
+use crate::{count::{LanguageStats, LineCounter, LineKind, Scanned, SkipReason}, lang::Languages, origin::ContentScan};
+
+/// # Embedding
+/// 文件中嵌入其他语言代码的格式
+/// * Fences: Markdown中标记了语言的代码块，例如```` ```rust ````
+/// * Notebook: Jupyter notebook的代码单元，语言由kernel决定，Markdown单元计入Markdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Embedding{
+    Fences,
+    Notebook,
+}
+
+/// 嵌入的代码按语言汇总时使用的名称，例如`Python (embedded)`
+pub fn embedded_name(language: &str)->String{
+    format!("{} (embedded)", language)
+}
+
+/// 打开的代码块，counter为None表示没有标记语言或语言未知，其中的行仍属于Markdown
+struct Fence<'a>{
+    marker: u8,
+    len: usize,
+    counter: Option<(&'a str, LineCounter<'a>)>,
+}
+
+/// # Fences
+/// 逐行识别Markdown中的代码块，代码块中的行按标记的语言统计
+pub(crate) struct Fences<'a>{
+    languages: &'a Languages,
+    open: Option<Fence<'a>>,
+    stats: BTreeMap<String, LanguageStats>,
+}
+
+impl<'a> Fences<'a> {
+    pub(crate) fn new(languages: &'a Languages)->Fences<'a>{
+        Fences{ languages, open: None, stats: BTreeMap::new() }
+    }
+
+    /// 处理一行，属于嵌入的代码时返回语言名称与这一行的分类；围栏本身以及未知语言的代码块仍属于Markdown
+    pub(crate) fn classify(&mut self, line: &[u8])->Option<(&'a str, LineKind)>{
+        if let Some(fence) = &mut self.open{
+            if is_close(line, fence.marker, fence.len){
+                self.close();
+                return None;
+            }
+            let (name, counter) = fence.counter.as_mut()?;
+            return Some((name, counter.line(line)));
+        }
+        if let Some((marker, len, tag)) = open_fence(line){
+            let counter = self.languages.find_tag(&tag).map(|l| (l.name.as_str(), LineCounter::new(l)));
+            self.open = Some(Fence{ marker, len, counter });
+        }
+        None
+    }
+
+    fn close(&mut self){
+        if let Some(Fence{ counter: Some((name, counter)), .. }) = self.open.take(){
+            *self.stats.entry(name.to_string()).or_default() += counter.stats();
+        }
+    }
+
+    /// 没有关闭的代码块到文件末尾为止，每种语言的files为1
+    pub(crate) fn finish(mut self)->BTreeMap<String, LanguageStats>{
+        self.close();
+        for stats in self.stats.values_mut(){
+            stats.files = 1;
+        }
+        self.stats
+    }
+}
+
+/// 代码块的开始：最多3个空格缩进，至少3个`` ` ``或`~`，之后的第一个词是语言，
+/// 支持```` ```rust,ignore ````、```` ```{python} ````这样的写法
+fn open_fence(line: &[u8])->Option<(u8, usize, String)>{
+    let indent = line.iter().take_while(|b| **b == b' ').count();
+    if indent > 3{
+        return None;
+    }
+    let rest = &line[indent..];
+    let marker = *rest.first().filter(|b| matches!(b, b'`' | b'~'))?;
+    let len = rest.iter().take_while(|b| **b == marker).count();
+    let info = String::from_utf8_lossy(&rest[len..]);
+    if len < 3 || (marker == b'`' && info.contains('`')){
+        return None;
+    }
+    let tag = info.trim().trim_start_matches(['{', '.']).split(|c: char| c.is_whitespace() || c == ',' || c == '}').next().unwrap_or_default();
+    Some((marker, len, tag.to_string()))
+}
+
+/// 代码块的结束：与开始相同的字符，长度不少于开始，后面只有空白
+fn is_close(line: &[u8], marker: u8, len: usize)->bool{
+    let indent = line.iter().take_while(|b| **b == b' ').count();
+    let rest = &line[indent..];
+    let count = rest.iter().take_while(|b| **b == marker).count();
+    indent <= 3 && count >= len && rest[count..].iter().all(|b| b.is_ascii_whitespace())
+}
+
+/// notebook中用到的字段，其他字段（例如输出中的图片）在解析时直接跳过，不会保存在内存中
+#[derive(Debug, Default, Deserialize)]
+struct Notebook{
+    #[serde(default)]
+    cells: Vec<Cell>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Metadata{
+    #[serde(default)]
+    kernelspec: Option<Kernelspec>,
+    #[serde(default)]
+    language_info: Option<LanguageInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Kernelspec{
+    #[serde(default)]
+    language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LanguageInfo{
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cell{
+    cell_type: String,
+    #[serde(default)]
+    source: Source,
+}
+
+/// 单元的内容可以是一个字符串，也可以是按行拆分的字符串数组
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Source{
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Text(String::new())
+    }
+}
+
+/// 统计Jupyter notebook（nbformat 4）：代码单元按kernel的语言（默认Python）统计，Markdown单元计入Markdown，
+/// notebook本身的行数为0，所有行都作为嵌入的代码，位于测试文件夹中时按`FileStats::parts`计入测试代码
+pub(crate) fn scan_notebook<R: Read>(reader: R, languages: &Languages)->Result<Scanned, SkipReason>{
+    let notebook: Notebook = serde_json::from_reader(BufReader::new(reader)).map_err(|e| SkipReason::Unreadable(format!("notebook格式错误: {}", e)))?;
+    let kernel = notebook.metadata.kernelspec.and_then(|k| k.language)
+        .or(notebook.metadata.language_info.and_then(|l| l.name))
+        .unwrap_or_else(|| "python".to_string());
+    let mut scan = ContentScan::default();
+    let mut embedded: BTreeMap<String, LanguageStats> = BTreeMap::new();
+    for cell in notebook.cells.iter(){
+        let tag = match cell.cell_type.as_str() {
+            "code" => kernel.as_str(),
+            "markdown" => "markdown",
+            _ => continue,
+        };
+        let Some(language) = languages.find_tag(tag) else { continue };
+        let source = match &cell.source {
+            Source::Text(text) => text.clone(),
+            Source::Lines(lines) => lines.concat(),
+        };
+        let mut counter = LineCounter::new(language);
+        for line in source.lines(){
+            scan.line(line.as_bytes(), line.len());
+            counter.line(line.as_bytes());
+        }
+        let stats = embedded.entry(language.name.clone()).or_default();
+        *stats += counter.stats();
+        stats.files = 1;
+    }
+    Ok(Scanned{ stats: LanguageStats{ files: 1, ..Default::default() }, embedded, test: LanguageStats::default(), complexity: None, scan })
+}
+
+#[cfg(test)]
+mod test{
+    use super::{scan_notebook, Fences};
+    use crate::{count::{LanguageStats, SkipReason}, lang::Languages};
+
+    #[test]
+    fn test_fences(){
+        let languages = Languages::builtin();
+        let mut fences = Fences::new(&languages);
+        let content = "# 标题\n```rust\n// 注释\nfn main(){}\n```\n\n~~~~ {.python}\nprint(1)\n~~~\n\n~~~~\n```console\n$ ls\n```\n```py\n\n";
+        let markdown = content.lines().filter(|line| fences.classify(line.as_bytes()).is_none()).count();
+        let embedded = fences.finish();
+        assert_eq!(10, markdown);
+        assert_eq!(LanguageStats{ files: 1, code: 1, comment: 1, blank: 0 }, embedded["Rust"]);
+        assert_eq!(LanguageStats{ files: 1, code: 2, comment: 0, blank: 2 }, embedded["Python"]);
+    }
+
+    #[test]
+    fn test_notebook(){
+        let languages = Languages::builtin();
+        let notebook = r##"{
+            "cells": [
+                {"cell_type": "markdown", "source": ["# 分析\n", "说明"]},
+                {"cell_type": "code", "source": ["import numpy as np\n", "\n", "# 注释\n", "np.zeros(3)"], "outputs": [{"data": {"image/png": "iVBORw0KGgo="}}]},
+                {"cell_type": "code", "source": "x = 1\ny = 2\n"},
+                {"cell_type": "raw", "source": "raw"}
+            ],
+            "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+            "nbformat": 4
+        }"##;
+        let scanned = scan_notebook(notebook.as_bytes(), &languages).unwrap();
+        assert_eq!(LanguageStats{ files: 1, code: 0, comment: 0, blank: 0 }, scanned.stats);
+        assert_eq!(LanguageStats{ files: 1, code: 4, comment: 1, blank: 1 }, scanned.embedded["Python"]);
+        assert_eq!(2, scanned.embedded["Markdown"].lines());
+        assert!(matches!(scan_notebook(&b"{\"cells\": 1}"[..], &languages), Err(SkipReason::Unreadable(_))));
+    }
+}
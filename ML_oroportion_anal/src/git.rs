@@ -0,0 +1,151 @@
+use std::{io::{self, BufRead, BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}, process::{Command, Stdio}, thread};
+use anyhow::{bail, Context, Ok, Result};
+
+/// 在仓库中执行git命令，返回标准输出
+pub(crate) fn git(repo: &Path, args: &[&str])->Result<Vec<u8>>{
+    let output = Command::new("git").arg("-C").arg(repo).args(args).output().context("无法执行git")?;
+    if !output.status.success(){
+        bail!("git {}失败: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+/// # TreeEntry
+/// 某个版本中跟踪的一个文件
+/// * oid: 文件内容的对象id
+/// * path: 相对于仓库根目录的路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TreeEntry{
+    pub oid: String,
+    pub path: PathBuf,
+}
+
+/// repo相对于仓库根目录的路径，repo就是仓库根目录时为空路径
+pub(crate) fn show_prefix(repo: &Path)->Result<PathBuf>{
+    let out = git(repo, &["rev-parse", "--show-prefix"])?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&out).trim_end_matches('\n')))
+}
+
+/// 列出指定版本中prefix（相对于仓库根目录的文件夹，为空时是整个仓库）下跟踪的所有普通文件，
+/// 以及prefix上层每一层文件夹中的`.gitattributes`，path相对于仓库根目录，跳过子模块和符号链接
+pub(crate) fn ls_tree(repo: &Path, rev: &str, prefix: &Path)->Result<Vec<TreeEntry>>{
+    let mut pathspecs = Vec::new();
+    if !prefix.as_os_str().is_empty(){
+        pathspecs.push(prefix.to_string_lossy().to_string());
+        pathspecs.extend(prefix.ancestors().skip(1).map(|dir| dir.join(".gitattributes").to_string_lossy().to_string()));
+    }
+    // `--end-of-options`之后的rev与路径都不会被当作选项
+    let mut args = vec!["--literal-pathspecs", "ls-tree", "-r", "-z", "--full-tree", "--end-of-options", rev];
+    args.extend(pathspecs.iter().map(String::as_str));
+    let out = git(repo, &args)?;
+    let mut entries = Vec::new();
+    for record in out.split(|b| *b == 0).filter(|r| !r.is_empty()){
+        // <mode> SP <type> SP <oid> TAB <path>
+        let tab = record.iter().position(|b| *b == b'\t').context("无法解析git ls-tree的输出")?;
+        let meta = String::from_utf8_lossy(&record[..tab]);
+        let meta = meta.split_whitespace().collect::<Vec<_>>();
+        if meta.len() != 3 || meta[1] != "blob" || meta[0] == "120000"{
+            continue;
+        }
+        entries.push(TreeEntry{ oid: meta[2].to_string(), path: PathBuf::from(String::from_utf8_lossy(&record[tab + 1..]).as_ref()) });
+    }
+    Ok(entries)
+}
+
+/// 通过一个`git cat-file --batch`进程依次读取对象内容，每个对象调用一次f，f不需要读完全部内容
+pub(crate) fn read_blobs<F: FnMut(usize, &mut dyn Read)->Result<()>>(repo: &Path, oids: &[String], mut f: F)->Result<()>{
+    let mut child = Command::new("git").arg("-C").arg(repo).args(["cat-file", "--batch"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null())
+        .spawn().context("无法执行git")?;
+    let stdin = child.stdin.take().context("无法写入git cat-file")?;
+    let stdout = child.stdout.take().context("无法读取git cat-file")?;
+    let res = thread::scope(|scope| {
+        // 单独的线程写入对象id，避免两个管道互相等待
+        scope.spawn(move || {
+            let mut stdin = BufWriter::new(stdin);
+            for oid in oids.iter(){
+                writeln!(stdin, "{}", oid)?;
+            }
+            stdin.flush()
+        });
+        let res = (|| {
+            let mut reader = BufReader::new(stdout);
+            for (index, oid) in oids.iter().enumerate(){
+                // <oid> SP <type> SP <size> LF <contents> LF
+                let mut header = String::new();
+                reader.read_line(&mut header)?;
+                let size = header.split_whitespace().nth(2).and_then(|s| s.parse::<u64>().ok()).with_context(|| format!("无法读取git对象{}", oid))?;
+                let mut blob = (&mut reader).take(size);
+                f(index, &mut blob)?;
+                io::copy(&mut blob, &mut io::sink())?;
+                reader.read_exact(&mut [0; 1])?;
+            }
+            Ok(())
+        })();
+        // 出错时结束git进程，写入线程会因为管道关闭而退出
+        if res.is_err(){
+            let _ = child.kill();
+        }
+        res
+    });
+    child.wait()?;
+    res
+}
+
+#[cfg(test)]
+pub(crate) mod test{
+    use std::{fs, path::{Path, PathBuf}, process::Command};
+
+    use super::{ls_tree, read_blobs, show_prefix};
+    use crate::test::TempDir;
+
+    /// 在临时文件夹中执行git命令，提交者固定，方便测试
+    pub(crate) fn run_git(repo: &Path, args: &[&str]){
+        run_git_at(repo, args, "2024-01-01T00:00:00Z");
+    }
+
+    /// 指定提交时间执行git命令
+    pub(crate) fn run_git_at(repo: &Path, args: &[&str], time: &str){
+        let status = Command::new("git").arg("-C").arg(repo)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false", "-c", "init.defaultBranch=main"])
+            .args(args).env("GIT_AUTHOR_DATE", time).env("GIT_COMMITTER_DATE", time)
+            .output().unwrap();
+        assert!(status.status.success(), "{}", String::from_utf8_lossy(&status.stderr));
+    }
+
+    /// 创建一个空的临时仓库
+    pub(crate) fn init_repo(name: &str)->TempDir{
+        let repo = TempDir::new(&format!("git_{}", name));
+        run_git(&repo, &["init", "-q"]);
+        repo
+    }
+
+    #[test]
+    fn test_blobs(){
+        let repo = init_repo("blobs");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/main.rs"), "fn main(){}\n").unwrap();
+        fs::write(repo.join("big.txt"), "x".repeat(100000)).unwrap();
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "init"]);
+        let entries = ls_tree(&repo, "HEAD", Path::new("")).unwrap();
+        let mut contents = Vec::new();
+        read_blobs(&repo, &entries.iter().map(|e| e.oid.clone()).collect::<Vec<_>>(), |_, blob| {
+            // 只读取一部分内容，剩余的部分会被跳过
+            let mut buf = [0; 4];
+            blob.read_exact(&mut buf)?;
+            contents.push(buf.to_vec());
+            anyhow::Ok(())
+        }).unwrap();
+        let missing = ls_tree(&repo, "v9", Path::new(""));
+        let prefix = show_prefix(&repo.join("src")).unwrap();
+        let src = ls_tree(&repo, "HEAD", &prefix).unwrap();
+        let option = ls_tree(&repo, "--output=/dev/null", Path::new(""));
+        assert_eq!(vec![PathBuf::from("big.txt"), PathBuf::from("src/main.rs")], entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>());
+        assert_eq!(vec![b"xxxx".to_vec(), b"fn m".to_vec()], contents);
+        assert!(missing.is_err());
+        assert_eq!(PathBuf::from("src"), prefix);
+        assert_eq!(vec![PathBuf::from("src/main.rs")], src.iter().map(|e| e.path.clone()).collect::<Vec<_>>());
+        assert!(option.is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, str::FromStr};
+use anyhow::{bail, Context, Ok, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{count::LanguageStats, counter::Counter, git::{git, show_prefix}};
+
+/// # HistoryStep
+/// 选取提交的方式
+/// * Every(n): 从最新的提交开始每n个提交取一个
+/// * Day、Week: 每天、每周（从周一开始，UTC）取最后一个提交
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryStep{
+    Every(usize),
+    Day,
+    Week,
+}
+
+impl Default for HistoryStep {
+    fn default() -> Self {
+        HistoryStep::Every(1)
+    }
+}
+
+impl FromStr for HistoryStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "day" => std::result::Result::Ok(HistoryStep::Day),
+            "week" => std::result::Result::Ok(HistoryStep::Week),
+            n => match n.parse::<usize>() {
+                std::result::Result::Ok(n) if n > 0 => std::result::Result::Ok(HistoryStep::Every(n)),
+                _ => Err(format!("{}不是正整数、day或week", s)),
+            },
+        }
+    }
+}
+
+/// # HistoryPoint
+/// 一个提交的统计结果
+/// * commit、tree: 提交以及对应的树对象
+/// * time: 提交时间，unix时间戳
+/// * date: 提交日期（UTC），格式为`YYYY-MM-DD`
+/// * languages: 各语言的行数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryPoint{
+    pub commit: String,
+    pub tree: String,
+    pub time: i64,
+    pub date: String,
+    pub languages: BTreeMap<String, LanguageStats>,
+}
+
+/// 按树对象缓存的统计结果，options是统计选项的指纹，选项变化后缓存失效。
+/// 统计子文件夹时键为`树对象:子文件夹`，与整个仓库的结果分开保存
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryCache{
+    options: String,
+    trees: BTreeMap<String, BTreeMap<String, LanguageStats>>,
+}
+
+impl Counter {
+    /// 统计分支上按step选取的提交，只沿着第一个父提交回溯，按时间从早到晚返回。
+    /// 相同的树对象只统计一次，cache不为空时把结果保存到该文件，之后再次统计时直接读取
+    pub fn history(&self, repo: &Path, rev: &str, step: HistoryStep, cache: Option<&Path>)->Result<Vec<HistoryPoint>>{
+        let log = git(repo, &["log", "--first-parent", "--reverse", "--format=%H %T %ct", "--end-of-options", rev])?;
+        let mut commits = Vec::new();
+        for line in String::from_utf8_lossy(&log).lines(){
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            match parts[..] {
+                [commit, tree, time] => commits.push((commit.to_string(), tree.to_string(), time.parse::<i64>().context("无法解析提交时间")?)),
+                _ => bail!("无法解析git log的输出: {}", line),
+            }
+        }
+        let prefix = show_prefix(repo)?;
+        let key = |tree: &str| match prefix.as_os_str().is_empty() {
+            true => tree.to_string(),
+            false => format!("{}:{}", tree, prefix.display()),
+        };
+        let options = self.fingerprint();
+        let mut history_cache = match cache {
+            Some(path) if path.exists() => {
+                let content = fs::read_to_string(path).with_context(|| format!("无法读取文件{}", path.display()))?;
+                serde_json::from_str::<HistoryCache>(&content).ok().filter(|c| c.options == options).unwrap_or_default()
+            },
+            _ => HistoryCache::default(),
+        };
+        history_cache.options = options;
+        let mut changed = false;
+        let mut points = Vec::new();
+        for (commit, tree, time) in sample(commits, step){
+            let languages = match history_cache.trees.get(&key(&tree)) {
+                Some(languages) => languages.clone(),
+                None => {
+                    let languages = self.count_revision(repo, &tree)?.languages().into_iter().collect::<BTreeMap<_, _>>();
+                    history_cache.trees.insert(key(&tree), languages.clone());
+                    changed = true;
+                    languages
+                },
+            };
+            points.push(HistoryPoint{ commit, tree, time, date: date(time), languages });
+        }
+        if let (Some(path), true) = (cache, changed){
+            if let Some(dir) = path.parent(){
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, serde_json::to_string(&history_cache)?).with_context(|| format!("无法写入文件{}", path.display()))?;
+        }
+        Ok(points)
+    }
+}
+
+/// 默认的缓存文件，放在仓库的git目录中
+pub fn default_history_cache(repo: &Path)->Result<PathBuf>{
+    let dir = git(repo, &["rev-parse", "--absolute-git-dir"])?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&dir).trim()).join("ml_oroportion_anal").join("history.json"))
+}
+
+/// 从按时间排列的提交中选取，总是包含最新的提交
+fn sample(commits: Vec<(String, String, i64)>, step: HistoryStep)->Vec<(String, String, i64)>{
+    let len = commits.len();
+    let bucket = |time: i64| match step {
+        HistoryStep::Every(_) => 0,
+        HistoryStep::Day => time.div_euclid(86400),
+        // 1970-01-01是周四，加3天后按周一分组
+        HistoryStep::Week => (time.div_euclid(86400) + 3).div_euclid(7),
+    };
+    let mut res = Vec::new();
+    let mut iter = commits.into_iter().enumerate().peekable();
+    while let Some((index, commit)) = iter.next(){
+        let keep = match step {
+            HistoryStep::Every(n) => (len - 1 - index).is_multiple_of(n),
+            _ => iter.peek().is_none_or(|(_, next)| bucket(next.2) != bucket(commit.2)),
+        };
+        if keep{
+            res.push(commit);
+        }
+    }
+    res
+}
+
+/// unix时间戳对应的UTC日期
+fn date(time: i64)->String{
+    // 参考 http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = time.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod test{
+    use std::fs;
+
+    use super::{date, HistoryStep};
+    use crate::{counter::Counter, git::test::{init_repo, run_git_at}};
+
+    #[test]
+    fn test_date(){
+        assert_eq!("1970-01-01", date(0));
+        assert_eq!("2024-02-29", date(1709210096));
+        assert_eq!("1969-12-31", date(-1));
+        assert_eq!(Ok(HistoryStep::Every(5)), "5".parse());
+        assert!("0".parse::<HistoryStep>().is_err());
+    }
+
+    #[test]
+    fn test_history(){
+        let repo = init_repo("history");
+        let commits = [
+            ("2024-01-01T08:00:00Z", "fn a(){}\n"),
+            ("2024-01-01T20:00:00Z", "fn a(){}\nfn b(){}\n"),
+            ("2024-01-02T08:00:00Z", "fn a(){}\nfn b(){}\nfn c(){}\n"),
+            ("2024-01-09T08:00:00Z", "fn a(){}\nfn b(){}\nfn c(){}\nfn d(){}\n"),
+        ];
+        for (time, content) in commits{
+            fs::write(repo.join("main.rs"), content).unwrap();
+            run_git_at(&repo, &["add", "."], time);
+            run_git_at(&repo, &["commit", "-q", "-m", time], time);
+        }
+        let cache = repo.join(".git/test_cache.json");
+        let counter = Counter::new();
+        let code = |step: HistoryStep| counter.history(&repo, "HEAD", step, Some(&cache)).unwrap().iter().map(|p| (p.date.clone(), p.languages["Rust"].code)).collect::<Vec<_>>();
+        let every = code(HistoryStep::Every(2));
+        let day = code(HistoryStep::Day);
+        let week = code(HistoryStep::Week);
+        // 修改缓存中的结果，再次统计时应该直接读取缓存
+        let content = fs::read_to_string(&cache).unwrap().replace("\"code\":4", "\"code\":40");
+        fs::write(&cache, content).unwrap();
+        let cached = code(HistoryStep::Every(1));
+        let fresh = Counter::new().lang("Rust").history(&repo, "HEAD", HistoryStep::Every(1), Some(&cache)).unwrap();
+        assert_eq!(vec![("2024-01-01".to_string(), 2), ("2024-01-09".to_string(), 4)], every);
+        assert_eq!(vec![("2024-01-01".to_string(), 2), ("2024-01-02".to_string(), 3), ("2024-01-09".to_string(), 4)], day);
+        assert_eq!(vec![("2024-01-02".to_string(), 3), ("2024-01-09".to_string(), 4)], week);
+        assert_eq!(vec![1, 2, 3, 40], cached.iter().map(|c| c.1).collect::<Vec<_>>());
+        assert_eq!(4, fresh[3].languages["Rust"].code);
+    }
+
+    #[test]
+    fn test_history_subdir(){
+        let repo = init_repo("history_subdir");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("main.rs"), "fn a(){}\nfn b(){}\n").unwrap();
+        fs::write(repo.join("src/lib.rs"), "fn c(){}\n").unwrap();
+        run_git_at(&repo, &["add", "."], "2024-01-01T08:00:00Z");
+        run_git_at(&repo, &["commit", "-q", "-m", "init"], "2024-01-01T08:00:00Z");
+        let cache = repo.join(".git/test_cache.json");
+        let code = |dir: &std::path::Path| Counter::new().history(dir, "HEAD", HistoryStep::Every(1), Some(&cache)).unwrap()[0].languages["Rust"].code;
+        // 共用一个缓存文件时，子文件夹与整个仓库的结果互不影响
+        assert_eq!(3, code(&repo));
+        assert_eq!(1, code(&repo.join("src")));
+        assert_eq!(3, code(&repo));
+    }
+}
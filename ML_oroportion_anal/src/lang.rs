@@ -0,0 +1,354 @@
+use std::{fs::{self, File}, io::{self, Read, Seek, SeekFrom}, path::Path};
+use anyhow::{bail, Context, Ok, Result};
+use serde::{Deserialize, Serialize};
+use walkfile::{sniff, ContentType};
+
+use crate::{complexity::ComplexityRules, embedded::Embedding};
+
+/// 内置的语言定义表
+const BUILTIN: &str = include_str!("languages.toml");
+
+/// # Quote
+/// 字符串字面量的定界符
+/// * start/end: 开始、结束标记
+/// * escape: 是否支持`\`转义，默认支持
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quote{
+    pub start: String,
+    pub end: String,
+    #[serde(default = "default_escape")]
+    pub escape: bool,
+}
+
+fn default_escape()->bool{
+    true
+}
+
+/// # Language
+/// 一种语言的定义
+/// * name: 语言名称，统计结果按名称汇总
+/// * extensions: 文件后缀，不带`.`
+/// * filenames: 完整的文件名，例如`Makefile`、`Dockerfile`、`CMakeLists.txt`
+/// * line_comments: 行注释，例如`//`、`#`
+/// * block_comments: 块注释，例如`/* */`
+/// * nested: 块注释是否可以嵌套（rust中`/* /* */ */`是一个注释）
+/// * quotes: 字符串定界符，长的要排在前面，字符串中的注释标记会被忽略
+/// * char_literals: 是否有`'a'`这样的字符字面量，rust中的生命周期`'a`不会被当作字符
+/// * shebangs: shebang中的解释器名称，例如`python3`
+/// * embedded: 文件中嵌入其他语言代码的格式，`fences`为Markdown的代码块，`notebook`为Jupyter的代码单元
+/// * heuristics: 多种语言使用同一个后缀（例如`.h`、`.m`）时，内容中出现的标记越多越优先
+/// * complexity: 计算函数复杂度的规则，没有时不计算
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Language{
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub filenames: Vec<String>,
+    pub line_comments: Vec<String>,
+    pub block_comments: Vec<(String, String)>,
+    pub nested: bool,
+    pub quotes: Vec<Quote>,
+    pub char_literals: bool,
+    pub shebangs: Vec<String>,
+    pub embedded: Option<Embedding>,
+    pub heuristics: Vec<String>,
+    pub complexity: Option<ComplexityRules>,
+}
+
+/// 根据内容判断语言时读取文件开头的字节数
+pub(crate) const SAMPLE_HEAD_LEN: usize = 8 * 1024;
+/// 根据内容判断语言时读取文件结尾的字节数，用于查找文件末尾的modeline
+pub(crate) const SAMPLE_TAIL_LEN: usize = 1024;
+
+/// 从reader中取出用于判断语言的样本：较短的内容全部保留，否则为开头与结尾，中间用换行隔开。
+/// 开头之后只保留最后读到的内容，内存占用与文件大小无关
+pub(crate) fn sample(reader: &mut dyn Read)->io::Result<Vec<u8>>{
+    let mut head = Vec::new();
+    (&mut *reader).take(SAMPLE_HEAD_LEN as u64).read_to_end(&mut head)?;
+    let mut tail = Vec::new();
+    let mut rest = 0;
+    let mut buf = [0; SAMPLE_TAIL_LEN];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            res => res?,
+        };
+        if n == 0{
+            break;
+        }
+        rest += n;
+        tail.extend_from_slice(&buf[..n]);
+        if tail.len() > 2 * SAMPLE_TAIL_LEN{
+            tail.drain(..tail.len() - SAMPLE_TAIL_LEN);
+        }
+    }
+    if rest <= SAMPLE_TAIL_LEN{
+        head.extend_from_slice(&tail);
+        return io::Result::Ok(head);
+    }
+    io::Result::Ok([head.as_slice(), b"\n", &tail[tail.len() - SAMPLE_TAIL_LEN..]].concat())
+}
+
+/// 与`sample`相同，只读取文件的开头与结尾
+pub(crate) fn read_sample(path: &Path)->io::Result<Vec<u8>>{
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+    let mut head = Vec::new();
+    if len <= SAMPLE_HEAD_LEN + SAMPLE_TAIL_LEN{
+        file.read_to_end(&mut head)?;
+        return io::Result::Ok(head);
+    }
+    (&mut file).take(SAMPLE_HEAD_LEN as u64).read_to_end(&mut head)?;
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::End(-(SAMPLE_TAIL_LEN as i64)))?;
+    file.read_to_end(&mut tail)?;
+    io::Result::Ok([head.as_slice(), b"\n", tail.as_slice()].concat())
+}
+
+/// 语言定义文件的格式，toml中为`[[language]]`，json中为`{"language": [...]}`
+#[derive(Debug, Deserialize)]
+struct LanguageFile{
+    #[serde(default)]
+    language: Vec<Language>,
+}
+
+/// # Languages
+/// 语言定义表，后加入的定义优先
+#[derive(Debug, Clone)]
+pub struct Languages{
+    languages: Vec<Language>,
+}
+
+impl Languages {
+    /// 内置的语言定义表
+    pub fn builtin()->Languages{
+        let languages = parse(BUILTIN, "toml").expect("内置的语言定义表格式错误");
+        Languages{ languages }
+    }
+
+    /// 从toml或json文件中加载语言定义，与已有语言同名时替换整个定义，否则新增
+    pub fn load(&mut self, path: &Path)->Result<()>{
+        let format = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+        let content = fs::read_to_string(path).with_context(|| format!("无法读取语言定义文件{}", path.display()))?;
+        let languages = parse(&content, &format).with_context(|| format!("语言定义文件{}格式错误", path.display()))?;
+        for language in languages{
+            self.insert(language);
+        }
+        Ok(())
+    }
+
+    /// 新增或替换一种语言
+    pub fn insert(&mut self, language: Language){
+        self.languages.retain(|l| l.name != language.name);
+        self.languages.push(language);
+    }
+
+    pub fn iter(&self)->std::slice::Iter<'_, Language>{
+        self.languages.iter()
+    }
+
+    /// 根据代码块的标记（例如```` ```rust ````、```` ```py ````）查找语言，不区分大小写，先匹配名称，再匹配后缀
+    pub fn find_tag(&self, tag: &str)->Option<&Language>{
+        self.iter().rev().find(|l| l.name.eq_ignore_ascii_case(tag))
+            .or_else(|| self.iter().rev().find(|l| l.extensions.iter().any(|e| e.eq_ignore_ascii_case(tag))))
+    }
+
+    /// 根据shebang中的解释器查找语言，`python3.11`这样带版本号的名称去掉版本号后再匹配一次
+    pub fn find_interpreter(&self, interpreter: &str)->Option<&Language>{
+        let find = |name: &str| self.iter().rev().find(|l| l.shebangs.iter().any(|s| s == name));
+        find(interpreter).or_else(|| find(interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')))
+    }
+
+    /// 根据文件名判断语言，先匹配完整文件名，再匹配后缀（区分大小写优先），多种语言使用同一个后缀时取后定义的语言
+    pub fn detect(&self, path: &Path)->Option<&Language>{
+        self.candidates(path).pop()
+    }
+
+    /// 需要`detect_content`根据内容判断：没有后缀并且不是已知的文件名时根据modeline与shebang判断，
+    /// 后缀被多种语言使用（例如`.h`、`.m`）时根据`heuristics`判断，后缀未知的文件不读取内容
+    pub fn needs_content(&self, path: &Path)->bool{
+        match path.extension() {
+            None => self.candidates(path).is_empty(),
+            Some(_) => self.candidates(path).len() > 1,
+        }
+    }
+
+    /// 根据文件名与内容判断语言，sample为文件开头（以及结尾）的内容：
+    /// 文件名与后缀能确定唯一的语言时直接使用；多种语言使用同一个后缀时根据`heuristics`选择；
+    /// 没有后缀并且不是已知的文件名时依次根据modeline（`vim: set ft=python:`、`-*- mode: ruby -*-`）与shebang判断。
+    /// 后缀未知的文件`needs_content`为false，统计时不会读取内容
+    pub fn detect_content(&self, path: &Path, sample: &[u8])->Option<&Language>{
+        let candidates = self.candidates(path);
+        if candidates.len() > 1{
+            let text = String::from_utf8_lossy(sample);
+            let score = |l: &Language| l.heuristics.iter().filter(|h| text.contains(h.as_str())).count();
+            let best = candidates.iter().map(|l| score(l)).max().unwrap_or_default();
+            return candidates.into_iter().rev().find(|l| score(l) == best);
+        }
+        if let Some(language) = candidates.into_iter().next(){
+            return Some(language);
+        }
+        let ContentType::Text{ interpreter, .. } = sniff(&sample[..sample.len().min(walkfile::SNIFF_LEN)]) else { return None };
+        modeline(sample).and_then(|mode| self.find_tag(&mode))
+            .or_else(|| interpreter.and_then(|i| self.find_interpreter(&i)))
+    }
+
+    /// 根据文件名与后缀得到的候选语言，按定义的先后排列，完整文件名匹配时只有一个候选
+    fn candidates(&self, path: &Path)->Vec<&Language>{
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else { return Vec::new() };
+        if let Some(language) = self.iter().rev().find(|l| l.filenames.iter().any(|f| f == file_name)){
+            return vec![language];
+        }
+        let Some(extension) = path.extension().and_then(|e| e.to_str()) else { return Vec::new() };
+        let candidates = self.iter().filter(|l| l.extensions.iter().any(|e| e == extension)).collect::<Vec<_>>();
+        if !candidates.is_empty(){
+            return candidates;
+        }
+        self.iter().filter(|l| l.extensions.iter().any(|e| e.eq_ignore_ascii_case(extension))).collect()
+    }
+}
+
+/// 从前两行的Emacs modeline或前后5行的Vim modeline中取出语言（mode、filetype）
+fn modeline(sample: &[u8])->Option<String>{
+    let text = String::from_utf8_lossy(sample);
+    let lines = text.lines().collect::<Vec<_>>();
+    // -*- python -*-、-*- mode: python; coding: utf-8 -*-
+    for line in lines.iter().take(2){
+        let Some((_, rest)) = line.split_once("-*-") else { continue };
+        let Some((inner, _)) = rest.split_once("-*-") else { continue };
+        if !inner.contains(':'){
+            return Some(inner.trim().to_string());
+        }
+        let mode = inner.split(';').filter_map(|p| p.split_once(':')).find(|(k, _)| k.trim().eq_ignore_ascii_case("mode"));
+        if let Some((_, mode)) = mode{
+            return Some(mode.trim().to_string());
+        }
+    }
+    // vim: set ft=python:、vi: filetype=sh
+    for line in lines.iter().take(5).chain(lines.iter().skip(lines.len().saturating_sub(5).max(5))){
+        for marker in ["vim:", "vi:", "ex:"]{
+            let Some(pos) = line.find(marker) else { continue };
+            if pos > 0 && !line[..pos].ends_with(char::is_whitespace){
+                continue;
+            }
+            let value = line[pos + marker.len()..].split(|c: char| c.is_whitespace() || c == ':')
+                .find_map(|t| ["ft=", "filetype=", "syntax=", "syn="].iter().find_map(|k| t.strip_prefix(k)));
+            if let Some(value) = value.filter(|v| !v.is_empty()){
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+fn parse(content: &str, format: &str)->Result<Vec<Language>>{
+    let file: LanguageFile = match format {
+        "toml" => toml::from_str(content)?,
+        "json" => serde_json::from_str(content)?,
+        _ => bail!("语言定义文件只支持toml与json格式"),
+    };
+    for language in file.language.iter(){
+        if language.name.is_empty(){
+            bail!("语言定义缺少name");
+        }
+    }
+    Ok(file.language)
+}
+
+#[cfg(test)]
+mod test{
+    use std::{fs, io::Read, path::Path};
+
+    use super::{sample, Languages, SAMPLE_HEAD_LEN, SAMPLE_TAIL_LEN};
+    use crate::test::TempDir;
+
+    #[test]
+    fn test_builtin(){
+        let languages = Languages::builtin();
+        assert!(languages.iter().count() > 30);
+        assert_eq!("Rust", languages.detect(Path::new("src/main.rs")).unwrap().name);
+        assert_eq!("Makefile", languages.detect(Path::new("Makefile")).unwrap().name);
+        assert_eq!("CMake", languages.detect(Path::new("a/CMakeLists.txt")).unwrap().name);
+        assert_eq!("Python", languages.detect(Path::new("SETUP.PY")).unwrap().name);
+        let python = languages.iter().find(|l| l.name == "Python").unwrap();
+        assert!(python.shebangs.contains(&"python3".to_string()));
+        assert!(languages.detect(Path::new("README")).is_none());
+        assert_eq!("Rust", languages.find_tag("rs").unwrap().name);
+        assert_eq!("C++", languages.find_tag("c++").unwrap().name);
+        assert_eq!("Shell", languages.find_tag("bash").unwrap().name);
+        assert!(languages.find_tag("console").is_none());
+    }
+
+    #[test]
+    fn test_content(){
+        let languages = Languages::builtin();
+        let detect = |path: &str, content: &str| languages.detect_content(Path::new(path), content.as_bytes()).map(|l| l.name.as_str());
+        assert_eq!(Some("Python"), detect("bin/run", "#!/usr/bin/env python3\nprint(1)\n"));
+        assert_eq!(Some("Python"), detect("bin/run", "#!/usr/local/bin/python3.11 -u\n"));
+        assert_eq!(Some("Shell"), detect("configure", "#!/bin/sh\nset -e\n"));
+        assert_eq!(Some("Ruby"), detect("tasks", "# -*- mode: ruby; coding: utf-8 -*-\nputs 1\n"));
+        assert_eq!(Some("Python"), detect("script", "# -*- python -*-\n"));
+        assert_eq!(Some("Shell"), detect("setup-env", &format!("export A=1\n{}# vim: set ft=sh ts=4:\n", "\n".repeat(20))));
+        assert_eq!(None, detect("notes", "novim: ft=sh\n"));
+        assert_eq!(None, detect("README", "说明\n"));
+        assert_eq!(None, detect("data", "#!/usr/bin/env python3\n\0\0\0"));
+        // 模棱两可的后缀
+        assert!(languages.needs_content(Path::new("a.h")) && !languages.needs_content(Path::new("a.rs")));
+        assert!(languages.needs_content(Path::new("bin/run")) && !languages.needs_content(Path::new("Makefile")));
+        assert!(!languages.needs_content(Path::new("data.unknown")) && !languages.needs_content(Path::new("env.local")));
+        assert_eq!(Some("C"), detect("a.h", "#include <stdio.h>\nint add(int a, int b);\n"));
+        assert_eq!(Some("C++"), detect("a.h", "namespace ml {\ntemplate <typename T> class Box;\n}\n"));
+        assert_eq!(Some("Objective-C"), detect("a.h", "#import <Foundation/Foundation.h>\n@interface A : NSObject\n@end\n"));
+        assert_eq!(Some("Objective-C"), detect("a.m", "@implementation A\n@end\n"));
+        assert_eq!(Some("MATLAB"), detect("a.m", "function y = f(x)\n% 注释\ny = zeros(3);\nend\n"));
+        assert_eq!(Some("Rust"), detect("main.rs", "#!/usr/bin/env python3\n"));
+        assert_eq!("C", languages.detect(Path::new("a.h")).unwrap().name);
+        // 样本包含开头与结尾
+        let long = format!("{}{}", "a".repeat(SAMPLE_HEAD_LEN), "b".repeat(SAMPLE_TAIL_LEN * 2));
+        let sampled = sample(&mut long.as_bytes()).unwrap();
+        assert_eq!(SAMPLE_HEAD_LEN + SAMPLE_TAIL_LEN + 1, sampled.len());
+        assert!(sampled.starts_with(b"aaa") && sampled.ends_with(b"bbb"));
+        assert_eq!(b"abc".to_vec(), sample(&mut &b"abc"[..]).unwrap());
+        let exact = format!("{}{}", "a".repeat(SAMPLE_HEAD_LEN), "b".repeat(SAMPLE_TAIL_LEN));
+        assert_eq!(exact.as_bytes(), sample(&mut exact.as_bytes()).unwrap());
+        // 分多次读取时结尾仍然是最后的内容
+        let mut chunked = long.as_bytes().chain(&b"cd"[..]);
+        let sampled = sample(&mut chunked).unwrap();
+        assert_eq!(SAMPLE_HEAD_LEN + SAMPLE_TAIL_LEN + 1, sampled.len());
+        assert!(sampled.ends_with(b"bbcd"));
+    }
+
+    #[test]
+    fn test_load(){
+        let dir = TempDir::new("languages");
+        let toml_path = dir.join("languages.toml");
+        fs::write(&toml_path, r##"
+[[language]]
+name = "Rust"
+extensions = ["rs", "rs.in"]
+line_comments = ["//"]
+
+[[language]]
+name = "Jsonnet"
+extensions = ["jsonnet", "libsonnet"]
+line_comments = ["//", "#"]
+block_comments = [["/*", "*/"]]
+"##).unwrap();
+        let json_path = dir.join("languages.json");
+        fs::write(&json_path, r##"{"language": [{"name": "Starlark", "filenames": ["BUILD", "WORKSPACE"], "line_comments": ["#"]}]}"##).unwrap();
+        let bad_path = dir.join("languages.yaml");
+        fs::write(&bad_path, "").unwrap();
+
+        let mut languages = Languages::builtin();
+        let count = languages.iter().count();
+        languages.load(&toml_path).unwrap();
+        languages.load(&json_path).unwrap();
+        assert!(languages.load(&bad_path).is_err());
+
+        assert_eq!(count + 2, languages.iter().count());
+        let rust = languages.iter().find(|l| l.name == "Rust").unwrap();
+        assert!(rust.block_comments.is_empty());
+        assert_eq!("Jsonnet", languages.detect(Path::new("a.libsonnet")).unwrap().name);
+        assert_eq!("Starlark", languages.detect(Path::new("BUILD")).unwrap().name);
+    }
+}
@@ -0,0 +1,78 @@
+//! 统计代码行数的库，命令行工具`ML_oroportion_anal`只是它的一层包装
+//! ```rust
+//! use std::path::PathBuf;
+//! use ml_oroportion_anal::{Counter, OutputFormat, SortKey, render};
+//! let report = Counter::new().exclude("target/").threads(2).count(&[PathBuf::from("./")]).unwrap();
+//! let total = report.total();
+//! assert_eq!(total.lines(), total.code + total.comment + total.blank);
+//! println!("{}", render(&report.languages(), OutputFormat::Table, SortKey::Code).unwrap());
+//! ```
+mod blame;
+mod breakdown;
+mod complexity;
+mod count;
+mod counter;
+mod diff;
+mod embedded;
+mod git;
+mod history;
+mod lang;
+mod origin;
+mod output;
+mod policy;
+mod report;
+mod testcode;
+
+pub use blame::{AuthorStats, BlameKey};
+pub use breakdown::{by_directory, by_language, top_files, Breakdown, DirStats, FileStats};
+pub use complexity::{complexity_summary, Blocks, Complexity, ComplexityRules, ComplexitySummary, FileComplexity, FunctionComplexity, FunctionOffender, LanguageComplexity};
+pub use count::{count_reader, LanguageStats, LineCounter, LineKind, SkipReason, SkippedFile, BUFFER_LEN, MAX_LINE_LEN};
+pub use counter::Counter;
+pub use diff::{diff, Diff, FileChange, FileDelta, LanguageDelta};
+pub use embedded::{embedded_name, Embedding};
+pub use history::{default_history_cache, HistoryPoint, HistoryStep};
+pub use lang::{Language, Languages, Quote};
+pub use origin::{Origin, GENERATED_BUCKET, GENERATED_MARKERS, HEADER_LINES, LONG_LINE_LEN};
+pub use output::{render, render_blame, render_complexity, render_diff, render_directories, render_files, render_history, render_violations, DirRow, FileRow, OutputFormat, Row, SortKey, StatsRow};
+pub use policy::{Policy, Rule, Violation};
+pub use report::Report;
+pub use testcode::test_name;
+
+#[cfg(test)]
+pub(crate) mod test{
+    use std::{fs, ops::Deref, path::{Path, PathBuf}};
+
+    /// # TempDir
+    /// 测试使用的临时文件夹，离开作用域时删除，断言失败导致panic时也会清理
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// 创建名称中带有进程id的空文件夹，路径是规范化后的
+        pub(crate) fn new(name: &str)->TempDir{
+            let dir = std::env::temp_dir().join(format!("ml_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(fs::canonicalize(dir).unwrap())
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
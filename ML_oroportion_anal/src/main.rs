@@ -1,103 +1,159 @@
-use std::{collections::HashMap, env, ffi::OsStr, fs::{self, File}, io::Read, path::PathBuf, str::FromStr};
-use anyhow::{Ok, Result};
+use std::{path::{self, Path, PathBuf}, process::ExitCode};
+use anyhow::{bail, Ok, Result};
+use clap::Parser;
+use ml_oroportion_anal::{complexity_summary, default_history_cache, diff, render, render_blame, render_complexity, render_diff, render_directories, render_files, render_history, render_violations, Breakdown, Counter, Languages, OutputFormat, Policy, Report};
 
-const FILE_NAME:[&str;4] = ["py", "rs", "go","java"];
-fn main()->Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2{
-        panic!("必须指定参数")
+mod cli;
+
+use cli::Cli;
+
+/// `--check`发现违规时的退出码，与出错时的1、参数错误时的2区分
+const VIOLATION_EXIT_CODE: u8 = 3;
+
+/// `--complexity`没有指定`--top`时输出的文件数与函数数
+const DEFAULT_COMPLEXITY_TOP: usize = 10;
+
+fn main()->ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Err(e) => {
+            eprintln!("错误: {:#}", e);
+            ExitCode::FAILURE
+        },
+        std::result::Result::Ok(code) => code,
+    }
+}
+
+/// 根据命令行参数设置`Counter`
+fn counter(cli: &Cli)->Result<Counter>{
+    let mut languages = Languages::builtin();
+    for file in cli.language_files.iter(){
+        languages.load(file)?;
     }
-    let path = PathBuf::from_str(args[1].as_str())?;
-    if !path.exists(){
-        panic!("指定的文件夹不存在")
+    let mut counter = Counter::new().languages(languages).hidden(cli.hidden_policy()).ignore_files(!cli.no_ignore).generated(!cli.exclude_generated).complexity(cli.complexity);
+    for lang in cli.langs.iter(){
+        counter = counter.lang(lang);
     }
-    let mut state:HashMap<String, i32> = HashMap::new();
-    println!("开始统计指定目录下不同语言的代码行数");
-    statistics_line(&mut state, path)?;
-    let values = state.values().map(|v| *v).into_iter().collect::<Vec::<i32>>().into_iter().fold(0, |acc, x| acc + x);
-    if values == 0 {
-        println!("未统计到代码");
-        return Ok(());
+    for exclude in cli.excludes.iter(){
+        counter = counter.exclude(exclude);
     }
-    for (k, v) in state.iter(){
-        println!("{}有{}行占比 {}", k, v, format!("{:.2}%", (*v as f64) / (values as f64) * 100.0));
+    if let Some(threads) = cli.threads{
+        counter = counter.threads(threads);
     }
-    Ok(())
+    Ok(counter)
 }
 
-fn statistics_line(state: &mut HashMap<String, i32>, path: PathBuf)->Result<()>{
-    for entry in fs::read_dir(path)?{
-        let entry = entry?;
-        if is_hidden(&entry.path()){
-            continue;
-        }
-        if entry.file_type()?.is_dir(){
-            statistics_line(state, entry.path())?;
-        }
-        if entry.file_type()?.is_file(){
-            let path = entry.path();
-            let extension = match path.extension() {
-                None=> continue,
-                Some(a) => a,
-            };
-            FILE_NAME.iter().find(|file_name| OsStr::new(file_name) == extension).map(|file_name|->Result<()>{
-                write_lines(state, path, &file_name.to_string())?;
-                Ok(())
-            });
-        } 
+/// json文件读取保存的统计结果，其他路径重新统计
+fn load_or_count(counter: &Counter, path: &Path)->Result<Report>{
+    if path.is_file() && path.extension().is_some_and(|e| e == "json"){
+        return Report::load(path);
     }
-    Ok(())
+    counter.count(&[path.to_path_buf()])
+}
+
+/// git在仓库目录中执行，相对路径的mailmap需要先按当前目录转为绝对路径
+fn mailmap(cli: &Cli)->Result<Option<PathBuf>>{
+    Ok(cli.mailmap.as_deref().map(path::absolute).transpose()?)
 }
 
-fn write_lines(state: &mut HashMap<String, i32>, path: PathBuf, key: &String)-> Result<()>{
-    let mut f = File::open(path)?;
-    let mut v: i32 = 0;
-    let mut content = String::new();
-    f.read_to_string(&mut content)?;
-    for line in content.lines(){
-        if line.len() == 0{
-            continue;
+fn run(cli: &Cli)->Result<ExitCode>{
+    let counter = counter(cli)?;
+    if cli.history{
+        if cli.paths.len() != 1{
+            bail!("--history只能指定一个git仓库");
+        }
+        let repo = &cli.paths[0];
+        let cache = match &cli.cache {
+            Some(cache) => cache.clone(),
+            None => default_history_cache(repo)?,
+        };
+        let points = counter.history(repo, cli.rev.as_deref().unwrap_or("HEAD"), cli.step, Some(&cache))?;
+        print!("{}", render_history(&points, cli.output)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(key) = cli.blame{
+        if cli.paths.len() != 1{
+            bail!("--blame只能指定一个git仓库");
         }
-        v += 1
+        let authors = counter.blame(&cli.paths[0], cli.rev.as_deref().unwrap_or("HEAD"), key, mailmap(cli)?.as_deref(), cli.depth)?;
+        print!("{}", render_blame(&authors, cli.output)?);
+        return Ok(ExitCode::SUCCESS);
     }
-    if state.contains_key(key){
-        let value = state.get(key);
-        match value {
-            Some(res_v) => state.insert(key.clone(), res_v + v),
+    if cli.diff{
+        if cli.paths.len() != 2{
+            bail!("--diff需要指定两个目录或json文件");
+        }
+        let old = load_or_count(&counter, &cli.paths[0])?;
+        let new = load_or_count(&counter, &cli.paths[1])?;
+        print!("{}", render_diff(&diff(&old, &new), cli.output)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+    let report = match &cli.rev {
+        None => counter.count(&cli.paths)?,
+        Some(_) if cli.paths.len() != 1 => bail!("--rev只能指定一个git仓库"),
+        Some(rev) => counter.count_revision(&cli.paths[0], rev)?,
+    };
+    if let Some(path) = &cli.save{
+        report.save(path)?;
+    }
+    if let Some(path) = &cli.check{
+        let policy = Policy::load(path)?;
+        let baseline = match cli.baseline.as_ref().or(policy.baseline.as_ref()) {
+            Some(path) => Some(Report::load(path)?),
             None => None,
         };
-    }else{
-        state.insert(key.clone(), v);
+        let violations = policy.check(&report, baseline.as_ref())?;
+        print!("{}", render_violations(&violations, policy.rule.len(), cli.output)?);
+        return Ok(match violations.is_empty() {
+            true => ExitCode::SUCCESS,
+            false => ExitCode::from(VIOLATION_EXIT_CODE),
+        });
     }
-    Ok(())
-}
-
-fn is_hidden(path: &PathBuf)->bool{
-    // 以.开头的文件为隐藏文件 以.开头的文件为隐藏文件
-    match path.file_name(){
-        None => false,
-        Some(p) => {
-            let temp = p.to_str().unwrap_or_default();
-            let temp = String::from(temp);
-            if temp.starts_with("."){
-                return true
-            }
-            return false
+    if cli.output == OutputFormat::Text && !cli.complexity{
+        println!("开始统计指定目录下不同语言的代码行数");
+    }
+    let out = if cli.complexity{
+        render_complexity(&complexity_summary(&report.files, cli.top.unwrap_or(DEFAULT_COMPLEXITY_TOP)), cli.output)?
+    }else{
+        match cli.by {
+            Breakdown::Language => render(&report.languages(), cli.output, cli.sort)?,
+            Breakdown::File => render_files(&report.top_files(cli.sort, cli.top), cli.output)?,
+            Breakdown::Directory => render_directories(&report.directories(cli.depth, cli.sort, cli.top), cli.output)?,
+        }
+    };
+    print!("{}", out);
+    // 跳过的文件输出到标准错误，不影响json、csv等格式的解析
+    if !report.skipped.is_empty(){
+        eprintln!("跳过{}个文件：", report.skipped.len());
+        for file in report.skipped.iter(){
+            eprintln!("  {}: {}", file.path.display(), file.reason);
         }
     }
-    
+    Ok(ExitCode::SUCCESS)
 }
 
 #[cfg(test)]
 mod test{
-    use std::{path::PathBuf, str::FromStr};
+    use std::{env, path::PathBuf, str::FromStr};
 
-    use crate::is_hidden;
+    use clap::Parser;
+    use walkfile::is_hidden;
+
+    use super::{mailmap, Cli};
 
     #[test]
     fn test_is_hidden(){
         let path = PathBuf::from_str("./.vimrc").unwrap();
-        assert_eq!(false, is_hidden(&path));
-        assert_eq!(true, is_hidden(&path));
+        assert!(is_hidden(&path));
+        let path = PathBuf::from_str("./main.rs").unwrap();
+        assert!(!is_hidden(&path));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_mailmap(){
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", "repo", "--blame", "author", "--mailmap", "config/mailmap"]).unwrap();
+        assert_eq!(Some(env::current_dir().unwrap().join("config/mailmap")), mailmap(&cli).unwrap());
+        let cli = Cli::try_parse_from(["ML_oroportion_anal", "repo", "--blame", "author"]).unwrap();
+        assert_eq!(None, mailmap(&cli).unwrap());
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+use walkfile::Glob;
+
+/// # Origin
+/// 文件的来源，生成的代码与第三方代码按语言汇总时计入`GENERATED_BUCKET`
+/// * Source: 手写的代码（默认）
+/// * Generated: 生成的代码，例如protobuf的输出、lock文件、压缩后的js
+/// * Vendored: 第三方代码，例如`vendor/`、`node_modules/`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin{
+    #[default]
+    Source,
+    Generated,
+    Vendored,
+}
+
+impl Origin {
+    pub fn is_source(&self)->bool{
+        *self == Origin::Source
+    }
+}
+
+/// 生成的代码与第三方代码按语言汇总时使用的名称
+pub const GENERATED_BUCKET: &str = "Generated/Vendored";
+
+/// 在文件开头的`HEADER_LINES`行中出现时认为是生成的代码
+pub const GENERATED_MARKERS: [&str; 4] = ["@generated", "DO NOT EDIT", "Code generated by", "<auto-generated"];
+
+/// 只在前几行中查找`GENERATED_MARKERS`
+pub const HEADER_LINES: usize = 10;
+
+/// 超过该长度（字节）的行占了文件的一半以上时认为是生成或压缩后的代码，
+/// 手写的代码中偶尔出现的长行（例如较长的字符串常量）不影响判断
+pub const LONG_LINE_LEN: usize = 1000;
+
+/// 第三方代码所在的文件夹
+const VENDORED_DIRS: [&str; 8] = ["vendor", "vendors", "node_modules", "bower_components", "third_party", "third-party", "thirdparty", "Pods"];
+
+/// 生成的文件，lock文件以及protobuf等工具的输出，名称需要能在语言定义中找到（后缀或`filenames`）才会被统计
+const GENERATED_NAMES: [&str; 10] = [
+    "Cargo.lock", "package-lock.json", "npm-shrinkwrap.json", "yarn.lock", "pnpm-lock.yaml", "poetry.lock",
+    "Pipfile.lock", "composer.lock", "flake.lock", "packages.lock.json",
+];
+const GENERATED_SUFFIXES: [&str; 11] = [
+    ".min.js", ".min.css", ".pb.go", ".pb.cc", ".pb.h", "_pb2.py", "_pb2_grpc.py", ".pb.swift", ".g.dart", ".freezed.dart", ".designer.cs",
+];
+
+/// # ContentScan
+/// 统计时顺便记录的内容特征，用于判断生成的代码
+/// * marker: 开头几行中出现了`GENERATED_MARKERS`
+/// * bytes: 所有行的字节数，不包含换行符
+/// * long_bytes: 超过`LONG_LINE_LEN`的行的字节数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ContentScan{
+    pub(crate) marker: bool,
+    bytes: usize,
+    long_bytes: usize,
+    lines: usize,
+}
+
+impl ContentScan {
+    /// 记录一行，len为截断前的长度
+    pub(crate) fn line(&mut self, line: &[u8], len: usize){
+        if self.lines < HEADER_LINES && !self.marker{
+            let text = String::from_utf8_lossy(line);
+            self.marker = GENERATED_MARKERS.iter().any(|m| text.contains(m));
+        }
+        self.lines += 1;
+        self.bytes += len;
+        if len > LONG_LINE_LEN{
+            self.long_bytes += len;
+        }
+    }
+
+    fn generated(&self)->bool{
+        self.marker || (self.long_bytes > 0 && self.long_bytes * 2 >= self.bytes)
+    }
+}
+
+/// # OriginHint
+/// 根据路径与`.gitattributes`得到的判断，统计之后再结合内容得到最终的`Origin`
+/// * vendored: 是否为第三方代码
+/// * generated: 是否为生成的代码，None表示需要根据内容判断
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct OriginHint{
+    vendored: bool,
+    generated: Option<bool>,
+}
+
+impl OriginHint {
+    /// relative为相对于仓库根目录的路径，不在git仓库中时为输出的路径，`.gitattributes`中的设置优先
+    pub(crate) fn new(relative: &Path, attributes: LinguistAttributes)->OriginHint{
+        let name = relative.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let dirs = relative.parent().map(|p| p.iter().filter_map(|d| d.to_str()).collect::<Vec<_>>()).unwrap_or_default();
+        let vendored = dirs.iter().any(|d| VENDORED_DIRS.contains(d));
+        let generated = GENERATED_NAMES.contains(&name) || GENERATED_SUFFIXES.iter().any(|s| name.ends_with(s)) || dirs.contains(&"__generated__");
+        OriginHint{
+            vendored: attributes.vendored.unwrap_or(vendored),
+            generated: attributes.generated.or(generated.then_some(true)),
+        }
+    }
+
+    /// 结合内容得到最终的来源，第三方代码优先
+    pub(crate) fn finish(self, scan: &ContentScan)->Origin{
+        if self.vendored{
+            Origin::Vendored
+        }else if self.generated.unwrap_or_else(|| scan.generated()){
+            Origin::Generated
+        }else{
+            Origin::Source
+        }
+    }
+}
+
+/// `.gitattributes`中的`linguist-generated`、`linguist-vendored`，None表示没有设置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct LinguistAttributes{
+    generated: Option<bool>,
+    vendored: Option<bool>,
+}
+
+/// `.gitattributes`中的一行
+#[derive(Debug, Clone)]
+struct AttributeRule{
+    glob: Glob,
+    base: PathBuf,
+    attributes: LinguistAttributes,
+}
+
+/// # Attributes
+/// 从根目录到各个文件夹的`.gitattributes`中与linguist相关的规则，后面（更深）的规则优先
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Attributes{
+    rules: Vec<AttributeRule>,
+}
+
+impl Attributes {
+    /// 添加一个`.gitattributes`文件的内容，base为文件所在的文件夹，需要按从浅到深的顺序添加
+    pub(crate) fn add(&mut self, content: &str, base: &Path){
+        for line in content.lines(){
+            let mut parts = line.split_whitespace();
+            let Some(pattern) = parts.next().filter(|p| !p.starts_with('#')) else { continue };
+            let mut attributes = LinguistAttributes::default();
+            for part in parts{
+                let (name, value) = match part.split_once('=') {
+                    Some((name, value)) => (name, !matches!(value, "false" | "0")),
+                    None => match part.strip_prefix(['-', '!']) {
+                        Some(name) => (name, false),
+                        None => (part, true),
+                    },
+                };
+                match name {
+                    "linguist-generated" => attributes.generated = Some(value),
+                    "linguist-vendored" => attributes.vendored = Some(value),
+                    _ => {},
+                }
+            }
+            if attributes != LinguistAttributes::default(){
+                self.rules.push(AttributeRule{ glob: Glob::new(pattern), base: base.to_path_buf(), attributes });
+            }
+        }
+    }
+
+    /// relative为文件相对于根目录的路径
+    pub(crate) fn get(&self, relative: &Path)->LinguistAttributes{
+        let mut res = LinguistAttributes::default();
+        for rule in self.rules.iter().rev(){
+            let std::result::Result::Ok(path) = relative.strip_prefix(&rule.base) else { continue };
+            if rule.glob.matches(path, false){
+                res.generated = res.generated.or(rule.attributes.generated);
+                res.vendored = res.vendored.or(rule.attributes.vendored);
+            }
+        }
+        res
+    }
+}
+
+/// 遍历文件夹时按需读取各个文件夹中的`.gitattributes`，每个文件夹只读取一次。
+/// 根目录在git仓库中时从仓库根目录（包含`.git`的文件夹）开始读取，与统计某个版本时的结果一致
+#[derive(Debug, Default)]
+pub(crate) struct AttributesCache{
+    tops: HashMap<PathBuf, Option<PathBuf>>,
+    dirs: HashMap<PathBuf, Attributes>,
+}
+
+impl AttributesCache {
+    /// root为规范化的根目录，relative为文件相对于root的路径。
+    /// 返回文件相对于仓库根目录的路径（root不在git仓库中时为None）以及其中的linguist属性
+    pub(crate) fn get(&mut self, root: &Path, relative: &Path)->(Option<PathBuf>, LinguistAttributes){
+        let top = self.tops.entry(root.to_path_buf())
+            .or_insert_with(|| root.ancestors().find(|dir| dir.join(".git").exists()).map(Path::to_path_buf))
+            .clone();
+        let (base, path) = match &top {
+            None => (root.to_path_buf(), relative.to_path_buf()),
+            Some(top) => (top.clone(), root.strip_prefix(top).unwrap_or(Path::new("")).join(relative)),
+        };
+        let attributes = self.dir(&base, path.parent().unwrap_or(Path::new(""))).get(&path);
+        (top.map(|_| path), attributes)
+    }
+
+    fn dir(&mut self, root: &Path, relative: &Path)->&Attributes{
+        let key = root.join(relative);
+        if !self.dirs.contains_key(&key){
+            let mut attributes = match relative.parent() {
+                Some(parent) => self.dir(root, parent).clone(),
+                None => Attributes::default(),
+            };
+            if let std::result::Result::Ok(content) = fs::read_to_string(key.join(".gitattributes")){
+                attributes.add(&content, relative);
+            }
+            self.dirs.insert(key.clone(), attributes);
+        }
+        &self.dirs[&key]
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::Path;
+
+    use super::{Attributes, ContentScan, LinguistAttributes, Origin, OriginHint};
+
+    fn origin(path: &str, attributes: &Attributes, content: &str)->Origin{
+        let mut scan = ContentScan::default();
+        for line in content.lines(){
+            scan.line(line.as_bytes(), line.len());
+        }
+        OriginHint::new(Path::new(path), attributes.get(Path::new(path))).finish(&scan)
+    }
+
+    #[test]
+    fn test_origin(){
+        let none = Attributes::default();
+        assert_eq!(Origin::Source, origin("src/main.rs", &none, "fn main(){}\n"));
+        assert_eq!(Origin::Vendored, origin("vendor/github.com/x/y.go", &none, "package y\n"));
+        assert_eq!(Origin::Generated, origin("api/user.pb.go", &none, "package api\n"));
+        assert_eq!(Origin::Generated, origin("Cargo.lock", &none, ""));
+        assert_eq!(Origin::Generated, origin("web/yarn.lock", &none, "# yarn lockfile v1\n"));
+        assert_eq!(Origin::Generated, origin("static/app.min.js", &none, "var a=1;\n"));
+        assert_eq!(Origin::Generated, origin("src/__generated__/schema.ts", &none, "export type A = 1;\n"));
+        assert_eq!(Origin::Source, origin("src/vendor.rs", &none, "fn main(){}\n"));
+        assert_eq!(Origin::Generated, origin("api/user.go", &none, "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n"));
+        assert_eq!(Origin::Source, origin("api/user.go", &none, &format!("{}// @generated\n", "\n".repeat(10))));
+        assert_eq!(Origin::Generated, origin("static/app.js", &none, &"a;".repeat(600)));
+        assert_eq!(Origin::Generated, origin("static/app.js", &none, &format!("/*! v1.0 */\n{}\n//# sourceMappingURL=app.js.map\n", "a;".repeat(600))));
+        // 手写代码中的一个长行不影响判断
+        let handwritten = format!("{}const TABLE: &str = \"{}\";\n", "fn f(){ let x = 1; }\n".repeat(100), "x".repeat(1500));
+        assert_eq!(Origin::Source, origin("src/table.rs", &none, &handwritten));
+
+        let mut attributes = Attributes::default();
+        attributes.add("# linguist\n*.gen.rs linguist-generated\nvendor/** -linguist-vendored\ndocs/** linguist-vendored=true\n", Path::new(""));
+        attributes.add("keep.pb.go linguist-generated=false\n", Path::new("api"));
+        assert_eq!(Origin::Generated, origin("src/a.gen.rs", &attributes, ""));
+        assert_eq!(Origin::Source, origin("vendor/lib.go", &attributes, ""));
+        assert_eq!(Origin::Vendored, origin("docs/a/b.py", &attributes, ""));
+        assert_eq!(Origin::Source, origin("api/keep.pb.go", &attributes, "// @generated\n"));
+        assert_eq!(LinguistAttributes::default(), attributes.get(Path::new("src/main.rs")));
+    }
+}
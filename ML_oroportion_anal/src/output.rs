@@ -0,0 +1,756 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+use anyhow::{Ok, Result};
+use serde::Serialize;
+
+use crate::{blame::AuthorStats, breakdown::{DirStats, FileStats}, complexity::ComplexitySummary, count::LanguageStats, diff::{Diff, FileChange}, history::HistoryPoint, policy::Violation};
+
+/// # OutputFormat
+/// * Text: 逐行的文字说明（默认）
+/// * Table: 对齐的表格
+/// * Json/Csv/Markdown: 便于其他工具解析的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat{
+    #[default]
+    Text,
+    Table,
+    Json,
+    Csv,
+    #[cfg_attr(feature = "cli", value(alias = "md"))]
+    Markdown,
+}
+
+/// # SortKey
+/// 输出的排序方式，数值从大到小，相同时按名称排序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortKey{
+    #[default]
+    Code,
+    Lines,
+    Files,
+    Comment,
+    Blank,
+    Name,
+}
+
+impl SortKey {
+    /// 排序使用的数值，按名称排序时总是0
+    pub fn value(&self, stats: &LanguageStats)->u64{
+        match self {
+            SortKey::Code => stats.code,
+            SortKey::Lines => stats.lines(),
+            SortKey::Files => stats.files,
+            SortKey::Comment => stats.comment,
+            SortKey::Blank => stats.blank,
+            SortKey::Name => 0,
+        }
+    }
+}
+
+/// 一种语言（或合计）的一行输出
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Row{
+    pub language: String,
+    pub files: u64,
+    pub lines: u64,
+    pub code: u64,
+    pub comment: u64,
+    pub blank: u64,
+    /// 代码行占所有代码行的百分比
+    pub code_percent: f64,
+    /// 总行数占所有行的百分比
+    pub lines_percent: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a>{
+    languages: &'a [Row],
+    total: &'a Row,
+}
+
+/// 一个文件的一行输出
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileRow{
+    pub path: String,
+    pub language: String,
+    pub lines: u64,
+    pub code: u64,
+    pub comment: u64,
+    pub blank: u64,
+}
+
+/// 文件夹中一种语言（或合计）的统计
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsRow{
+    pub language: String,
+    pub files: u64,
+    pub lines: u64,
+    pub code: u64,
+    pub comment: u64,
+    pub blank: u64,
+}
+
+/// 一个文件夹的输出
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DirRow{
+    pub path: String,
+    pub total: StatsRow,
+    pub languages: Vec<StatsRow>,
+}
+
+impl StatsRow {
+    fn new(language: &str, stats: &LanguageStats)->StatsRow{
+        StatsRow{
+            language: language.to_string(),
+            files: stats.files,
+            lines: stats.lines(),
+            code: stats.code,
+            comment: stats.comment,
+            blank: stats.blank,
+        }
+    }
+
+    fn cells(&self, path: &str)->Vec<String>{
+        vec![path.to_string(), self.language.clone(), self.files.to_string(), self.lines.to_string(), self.code.to_string(), self.comment.to_string(), self.blank.to_string()]
+    }
+}
+
+impl FileRow {
+    pub fn new(file: &FileStats)->FileRow{
+        FileRow{
+            path: display_path(&file.path),
+            language: file.language.clone(),
+            lines: file.total().lines(),
+            code: file.total().code,
+            comment: file.total().comment,
+            blank: file.total().blank,
+        }
+    }
+}
+
+impl DirRow {
+    pub fn new(dir: &DirStats)->DirRow{
+        DirRow{
+            path: display_path(&dir.path),
+            total: StatsRow::new("Total", &dir.total),
+            languages: dir.languages.iter().map(|(language, stats)| StatsRow::new(language, stats)).collect(),
+        }
+    }
+}
+
+/// 根目录为当前目录时路径为空，显示为`.`
+fn display_path(path: &Path)->String{
+    match path.as_os_str().is_empty() {
+        true => ".".to_string(),
+        false => path.display().to_string(),
+    }
+}
+
+/// 按指定方式排序，数值相同时按名称从小到大，保证输出顺序稳定，并计算百分比与合计
+pub fn rows(state: &HashMap<String, LanguageStats>, sort: SortKey)->(Vec<Row>, Row){
+    let mut total = LanguageStats::default();
+    for stats in state.values(){
+        total += *stats;
+    }
+    let row = |language: &str, stats: &LanguageStats| Row{
+        language: language.to_string(),
+        files: stats.files,
+        lines: stats.lines(),
+        code: stats.code,
+        comment: stats.comment,
+        blank: stats.blank,
+        code_percent: percent(stats.code, total.code),
+        lines_percent: percent(stats.lines(), total.lines()),
+    };
+    let mut items = state.iter().collect::<Vec<_>>();
+    items.sort_by(|a, b| sort.value(b.1).cmp(&sort.value(a.1)).then_with(|| a.0.cmp(b.0)));
+    (items.into_iter().map(|(k, v)| row(k, v)).collect(), row("Total", &total))
+}
+
+pub(crate) fn percent(part: u64, total: u64)->f64{
+    if total == 0{
+        return 0.0;
+    }
+    // 保留两位小数，各种格式的输出保持一致
+    (part as f64 / total as f64 * 10000.0).round() / 100.0
+}
+
+/// 按指定格式输出统计结果
+pub fn render(state: &HashMap<String, LanguageStats>, format: OutputFormat, sort: SortKey)->Result<String>{
+    let (rows, total) = rows(state, sort);
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if total.code == 0{
+                out.push_str("未统计到代码\n");
+            }
+            for row in rows.iter(){
+                writeln!(out, "{}有{}行，代码{}行占比 {:.2}%，注释{}行，空行{}行", row.language, row.lines, row.code, row.code_percent, row.comment, row.blank)?;
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&JsonReport{ languages: &rows, total: &total })?;
+            out.push('\n');
+        },
+        OutputFormat::Csv => {
+            out.push_str("language,files,lines,code,comment,blank,code_percent,lines_percent\n");
+            for row in rows.iter().chain([&total]){
+                writeln!(out, "{},{},{},{},{},{},{:.2},{:.2}", csv_field(&row.language), row.files, row.lines, row.code, row.comment, row.blank, row.code_percent, row.lines_percent)?;
+            }
+        },
+        OutputFormat::Markdown | OutputFormat::Table => {
+            let header = ["语言", "文件数", "总行数", "代码", "注释", "空行", "代码占比", "行数占比"];
+            let cells = rows.iter().chain([&total]).map(|row| vec![
+                row.language.clone(), row.files.to_string(), row.lines.to_string(), row.code.to_string(),
+                row.comment.to_string(), row.blank.to_string(), format!("{:.2}%", row.code_percent), format!("{:.2}%", row.lines_percent),
+            ]).collect::<Vec<_>>();
+            write_grid(&mut out, format, &header, &cells, 1, true)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 按指定格式输出每个文件的统计结果
+pub fn render_files(files: &[&FileStats], format: OutputFormat)->Result<String>{
+    let rows = files.iter().map(|f| FileRow::new(f)).collect::<Vec<_>>();
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if rows.is_empty(){
+                out.push_str("未统计到代码\n");
+            }
+            for row in rows.iter(){
+                writeln!(out, "{}（{}）有{}行，代码{}行，注释{}行，空行{}行", row.path, row.language, row.lines, row.code, row.comment, row.blank)?;
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&serde_json::json!({ "files": rows }))?;
+            out.push('\n');
+        },
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Table => {
+            let header = match format {
+                OutputFormat::Csv => ["path", "language", "lines", "code", "comment", "blank"],
+                _ => ["路径", "语言", "总行数", "代码", "注释", "空行"],
+            };
+            let cells = rows.iter().map(|row| vec![
+                row.path.clone(), row.language.clone(), row.lines.to_string(), row.code.to_string(), row.comment.to_string(), row.blank.to_string(),
+            ]).collect::<Vec<_>>();
+            write_grid(&mut out, format, &header, &cells, 2, false)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 按指定格式输出每个文件夹的统计结果，表格类格式中每个文件夹先输出合计，再输出各语言
+pub fn render_directories(dirs: &[DirStats], format: OutputFormat)->Result<String>{
+    let rows = dirs.iter().map(DirRow::new).collect::<Vec<_>>();
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if rows.is_empty(){
+                out.push_str("未统计到代码\n");
+            }
+            for row in rows.iter(){
+                let languages = row.languages.iter().map(|l| format!("{} {}行", l.language, l.code)).collect::<Vec<_>>().join("，");
+                writeln!(out, "{}有{}行，代码{}行：{}", row.path, row.total.lines, row.total.code, languages)?;
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&serde_json::json!({ "directories": rows }))?;
+            out.push('\n');
+        },
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Table => {
+            let header = match format {
+                OutputFormat::Csv => ["path", "language", "files", "lines", "code", "comment", "blank"],
+                _ => ["路径", "语言", "文件数", "总行数", "代码", "注释", "空行"],
+            };
+            let cells = rows.iter().flat_map(|row| [&row.total].into_iter().chain(row.languages.iter()).map(|l| l.cells(&row.path))).collect::<Vec<_>>();
+            write_grid(&mut out, format, &header, &cells, 2, false)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 按指定格式输出两次统计结果的比较，先输出各语言的变化，再输出新增、删除、修改的文件
+pub fn render_diff(diff: &Diff, format: OutputFormat)->Result<String>{
+    let change = |change: FileChange| match change {
+        FileChange::Added => "新增",
+        FileChange::Removed => "删除",
+        FileChange::Changed => "修改",
+    };
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if diff.languages.is_empty(){
+                out.push_str("各语言行数没有变化\n");
+            }
+            for d in diff.languages.iter(){
+                writeln!(out, "{}：{} → {}行（{:+}），占比 {:.2}% → {:.2}%（{:+.2}个百分点）", d.language, d.old.lines(), d.new.lines(), d.lines, d.old_percent, d.new_percent, d.percent)?;
+            }
+            writeln!(out, "合计：{} → {}行（{:+}），代码{} → {}行（{:+}）", diff.total.old.lines(), diff.total.new.lines(), diff.total.lines, diff.total.old.code, diff.total.new.code, diff.total.code)?;
+            for kind in [FileChange::Added, FileChange::Removed, FileChange::Changed]{
+                let files = diff.files.iter().filter(|f| f.change == kind).collect::<Vec<_>>();
+                if files.is_empty(){
+                    continue;
+                }
+                writeln!(out, "{}文件：", change(kind))?;
+                for f in files{
+                    match kind {
+                        FileChange::Added => writeln!(out, "  + {}（{}）{}行", f.path.display(), f.language, f.new.lines())?,
+                        FileChange::Removed => writeln!(out, "  - {}（{}）{}行", f.path.display(), f.language, f.old.lines())?,
+                        FileChange::Changed => writeln!(out, "  ~ {}（{}）{} → {}行（{:+}），代码{:+}，注释{:+}，空行{:+}", f.path.display(), f.language, f.old.lines(), f.new.lines(), f.lines, f.code, f.comment, f.blank)?,
+                    }
+                }
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(diff)?;
+            out.push('\n');
+        },
+        OutputFormat::Csv => {
+            out.push_str("type,path,language,old_lines,new_lines,lines,code,comment,blank,old_percent,new_percent,percent\n");
+            for (kind, d) in diff.languages.iter().map(|d| ("language", d)).chain([("total", &diff.total)]){
+                writeln!(out, "{},,{},{},{},{},{},{},{},{:.2},{:.2},{:.2}", kind, csv_field(&d.language), d.old.lines(), d.new.lines(), d.lines, d.code, d.comment, d.blank, d.old_percent, d.new_percent, d.percent)?;
+            }
+            for f in diff.files.iter(){
+                let kind = serde_json::to_value(f.change)?;
+                writeln!(out, "{},{},{},{},{},{},{},{},{},,,", kind.as_str().unwrap_or_default(), csv_field(&f.path.display().to_string()), csv_field(&f.language), f.old.lines(), f.new.lines(), f.lines, f.code, f.comment, f.blank)?;
+            }
+        },
+        OutputFormat::Markdown | OutputFormat::Table => {
+            let header = ["语言", "原行数", "新行数", "变化", "代码", "注释", "空行", "原占比", "新占比", "占比变化"];
+            let cells = diff.languages.iter().chain([&diff.total]).map(|d| vec![
+                d.language.clone(), d.old.lines().to_string(), d.new.lines().to_string(), format!("{:+}", d.lines),
+                format!("{:+}", d.code), format!("{:+}", d.comment), format!("{:+}", d.blank),
+                format!("{:.2}%", d.old_percent), format!("{:.2}%", d.new_percent), format!("{:+.2}", d.percent),
+            ]).collect::<Vec<_>>();
+            write_grid(&mut out, format, &header, &cells, 1, true)?;
+            if !diff.files.is_empty(){
+                out.push('\n');
+                let header = ["路径", "语言", "状态", "原行数", "新行数", "变化", "代码", "注释", "空行"];
+                let cells = diff.files.iter().map(|f| vec![
+                    f.path.display().to_string(), f.language.clone(), change(f.change).to_string(),
+                    f.old.lines().to_string(), f.new.lines().to_string(), format!("{:+}", f.lines),
+                    format!("{:+}", f.code), format!("{:+}", f.comment), format!("{:+}", f.blank),
+                ]).collect::<Vec<_>>();
+                write_grid(&mut out, format, &header, &cells, 2, false)?;
+            }
+        },
+    }
+    Ok(out)
+}
+
+/// 按指定格式输出代码行数的历史，csv以及表格中每个提交的每种语言一行，方便绘制图表
+pub fn render_history(points: &[HistoryPoint], format: OutputFormat)->Result<String>{
+    // 每个提交中的语言按代码行从多到少排列
+    fn languages(point: &HistoryPoint)->Vec<(&String, &LanguageStats)>{
+        let mut languages = point.languages.iter().collect::<Vec<_>>();
+        languages.sort_by(|a, b| b.1.code.cmp(&a.1.code).then_with(|| a.0.cmp(b.0)));
+        languages
+    }
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if points.is_empty(){
+                out.push_str("没有找到提交\n");
+            }
+            for point in points.iter(){
+                let text = languages(point).iter().map(|(name, stats)| format!("{} {}行", name, stats.code)).collect::<Vec<_>>().join("，");
+                writeln!(out, "{} {}：{}", point.date, &point.commit[..point.commit.len().min(7)], text)?;
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&serde_json::json!({ "history": points }))?;
+            out.push('\n');
+        },
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Table => {
+            let header = match format {
+                OutputFormat::Csv => ["date", "commit", "language", "files", "lines", "code", "comment", "blank"],
+                _ => ["日期", "提交", "语言", "文件数", "总行数", "代码", "注释", "空行"],
+            };
+            let cells = points.iter().flat_map(|point| languages(point).into_iter().map(|(name, stats)| vec![
+                point.date.clone(), point.commit.clone(), name.clone(), stats.files.to_string(),
+                stats.lines().to_string(), stats.code.to_string(), stats.comment.to_string(), stats.blank.to_string(),
+            ])).collect::<Vec<_>>();
+            write_grid(&mut out, format, &header, &cells, 3, false)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 输出按作者汇总的blame结果，csv、markdown、表格中每个作者依次输出合计、各语言、各文件夹
+pub fn render_blame(authors: &[AuthorStats], format: OutputFormat)->Result<String>{
+    // 按代码行从多到少排列
+    fn sorted<K: Ord>(map: &std::collections::BTreeMap<K, LanguageStats>)->Vec<(&K, &LanguageStats)>{
+        let mut items = map.iter().collect::<Vec<_>>();
+        items.sort_by(|a, b| b.1.code.cmp(&a.1.code).then_with(|| a.0.cmp(b.0)));
+        items
+    }
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if authors.is_empty(){
+                out.push_str("没有找到作者\n");
+            }
+            for author in authors.iter(){
+                let languages = sorted(&author.languages).iter().map(|(name, stats)| format!("{} {}行", name, stats.code)).collect::<Vec<_>>().join("，");
+                let dirs = sorted(&author.directories).iter().map(|(path, stats)| format!("{} {}行", path.display(), stats.code)).collect::<Vec<_>>().join("，");
+                writeln!(out, "{}：代码{}行，注释{}行，空行{}行", author.author, author.total.code, author.total.comment, author.total.blank)?;
+                writeln!(out, "  语言：{}", languages)?;
+                writeln!(out, "  文件夹：{}", dirs)?;
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&serde_json::json!({ "authors": authors }))?;
+            out.push('\n');
+        },
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Table => {
+            let header = match format {
+                OutputFormat::Csv => ["author", "kind", "name", "files", "lines", "code", "comment", "blank"],
+                _ => ["作者", "类型", "名称", "文件数", "总行数", "代码", "注释", "空行"],
+            };
+            let kinds = match format {
+                OutputFormat::Csv => ["total", "language", "directory"],
+                _ => ["合计", "语言", "文件夹"],
+            };
+            let mut cells = Vec::new();
+            for author in authors.iter(){
+                let items = std::iter::once((kinds[0], String::new(), &author.total))
+                    .chain(sorted(&author.languages).into_iter().map(|(name, stats)| (kinds[1], name.clone(), stats)))
+                    .chain(sorted(&author.directories).into_iter().map(|(path, stats)| (kinds[2], path.display().to_string(), stats)));
+                for (kind, name, stats) in items{
+                    cells.push(vec![
+                        author.author.clone(), kind.to_string(), name, stats.files.to_string(),
+                        stats.lines().to_string(), stats.code.to_string(), stats.comment.to_string(), stats.blank.to_string(),
+                    ]);
+                }
+            }
+            write_grid(&mut out, format, &header, &cells, 3, false)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 输出复杂度：各语言的平均值以及圈复杂度最高的函数，csv、markdown、表格中先输出各语言，再输出各函数
+pub fn render_complexity(summary: &ComplexitySummary, format: OutputFormat)->Result<String>{
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if summary.languages.is_empty(){
+                out.push_str("没有可以计算复杂度的文件\n");
+            }
+            for language in summary.languages.iter(){
+                writeln!(out, "{}：{}个文件，{}个函数，平均每个文件{:.2}个函数，每个函数圈复杂度{:.2}（最大{}），嵌套{:.2}层（最大{}层）",
+                    language.language, language.files, language.functions, language.avg_functions,
+                    language.avg_cyclomatic, language.max_cyclomatic, language.avg_nesting, language.max_nesting)?;
+            }
+            if !summary.files.is_empty(){
+                writeln!(out, "圈复杂度最高的{}个文件：", summary.files.len())?;
+            }
+            for file in summary.files.iter(){
+                writeln!(out, "  {}（{}）：{}个函数，圈复杂度合计{}（最大{}），嵌套最大{}层", display_path(&file.path), file.language,
+                    file.functions, file.cyclomatic, file.max_cyclomatic, file.max_nesting)?;
+            }
+            if !summary.functions.is_empty(){
+                writeln!(out, "圈复杂度最高的{}个函数：", summary.functions.len())?;
+            }
+            for function in summary.functions.iter(){
+                writeln!(out, "  {}:{} {}（{}）：圈复杂度{}，嵌套{}层", display_path(&function.path), function.function.line,
+                    function.function.name, function.language, function.function.cyclomatic, function.function.nesting)?;
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(summary)?;
+            out.push('\n');
+        },
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Table => {
+            let header = match format {
+                OutputFormat::Csv => ["kind", "language", "path", "function", "line", "files", "functions", "cyclomatic", "nesting"],
+                _ => ["类型", "语言", "文件", "函数", "行", "文件数", "函数数", "圈复杂度", "嵌套"],
+            };
+            let kinds = match format {
+                OutputFormat::Csv => ["language", "file", "function"],
+                _ => ["平均", "文件", "函数"],
+            };
+            let languages = summary.languages.iter().map(|l| vec![
+                kinds[0].to_string(), l.language.clone(), String::new(), String::new(), String::new(),
+                l.files.to_string(), l.functions.to_string(), format!("{:.2}", l.avg_cyclomatic), format!("{:.2}", l.avg_nesting),
+            ]);
+            // 文件的圈复杂度为合计，嵌套为最大值
+            let files = summary.files.iter().map(|f| vec![
+                kinds[1].to_string(), f.language.clone(), display_path(&f.path), String::new(), String::new(),
+                String::new(), f.functions.to_string(), f.cyclomatic.to_string(), f.max_nesting.to_string(),
+            ]);
+            let functions = summary.functions.iter().map(|f| vec![
+                kinds[2].to_string(), f.language.clone(), display_path(&f.path), f.function.name.clone(), f.function.line.to_string(),
+                String::new(), String::new(), f.function.cyclomatic.to_string(), f.function.nesting.to_string(),
+            ]);
+            write_grid(&mut out, format, &header, &languages.chain(files).chain(functions).collect::<Vec<_>>(), 4, false)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 输出检查规则的结果，rules为检查的规则数
+pub fn render_violations(violations: &[Violation], rules: usize, format: OutputFormat)->Result<String>{
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            if violations.is_empty(){
+                writeln!(out, "检查{}条规则，全部通过", rules)?;
+            }else{
+                writeln!(out, "检查{}条规则，发现{}处违规：", rules, violations.len())?;
+            }
+            for violation in violations.iter(){
+                match &violation.path {
+                    Some(path) => writeln!(out, "  [{}] {}：{}", violation.rule, path.display(), violation.message)?,
+                    None => writeln!(out, "  [{}] {}", violation.rule, violation.message)?,
+                }
+            }
+        },
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&serde_json::json!({ "rules": rules, "violations": violations }))?;
+            out.push('\n');
+        },
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Table => {
+            let header = match format {
+                OutputFormat::Csv => ["rule", "path", "message"],
+                _ => ["规则", "文件", "说明"],
+            };
+            let cells = violations.iter().map(|v| vec![
+                v.rule.clone(), v.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default(), v.message.clone(),
+            ]).collect::<Vec<_>>();
+            write_grid(&mut out, format, &header, &cells, 3, false)?;
+        },
+    }
+    Ok(out)
+}
+
+/// 输出csv、markdown或对齐的表格，前left列是名称，左对齐，其余的数字右对齐；
+/// has_total为true时最后一行是合计，表格中用分隔线隔开
+fn write_grid(out: &mut String, format: OutputFormat, header: &[&str], cells: &[Vec<String>], left: usize, has_total: bool)->Result<()>{
+    match format {
+        OutputFormat::Csv => {
+            for line in [header.iter().map(|h| h.to_string()).collect()].iter().chain(cells.iter()){
+                writeln!(out, "{}", line.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","))?;
+            }
+        },
+        OutputFormat::Markdown => {
+            writeln!(out, "| {} |", header.join(" | "))?;
+            let align = (0..header.len()).map(|i| if i < left { ":---" } else { "---:" }).collect::<Vec<_>>();
+            writeln!(out, "|{}|", align.join("|"))?;
+            for line in cells.iter(){
+                writeln!(out, "| {} |", line.iter().map(|c| c.replace('|', "\\|")).collect::<Vec<_>>().join(" | "))?;
+            }
+        },
+        _ => {
+            let header = header.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+            let mut widths = header.iter().map(|h| display_width(h)).collect::<Vec<_>>();
+            for line in cells.iter(){
+                for (i, cell) in line.iter().enumerate(){
+                    widths[i] = widths[i].max(display_width(cell));
+                }
+            }
+            let separator = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  ");
+            let total_index = if has_total { cells.len() } else { usize::MAX };
+            for (index, line) in [header].iter().chain(cells.iter()).enumerate(){
+                if index == 1 || index == total_index{
+                    writeln!(out, "{}", separator)?;
+                }
+                let text = line.iter().enumerate().map(|(i, cell)| {
+                    let pad = " ".repeat(widths[i] - display_width(cell));
+                    // 名称左对齐，数字右对齐
+                    if i < left { format!("{}{}", cell, pad) } else { format!("{}{}", pad, cell) }
+                }).collect::<Vec<_>>().join("  ");
+                writeln!(out, "{}", text.trim_end())?;
+            }
+        },
+    }
+    Ok(())
+}
+
+/// 包含逗号、引号、换行的字段需要用引号包起来
+fn csv_field(value: &str)->String{
+    if value.contains([',', '"', '\n']){
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    value.to_string()
+}
+
+/// 终端中的显示宽度，中文等宽字符占两格
+fn display_width(value: &str)->usize{
+    value.chars().map(|c| if (c as u32) >= 0x1100 { 2 } else { 1 }).sum()
+}
+
+#[cfg(test)]
+mod test{
+    use std::collections::HashMap;
+
+    use std::path::PathBuf;
+
+    use super::{render, render_blame, render_complexity, render_diff, render_directories, render_files, render_history, render_violations, OutputFormat, SortKey};
+    use crate::{blame::AuthorStats, breakdown::{DirStats, FileStats}, complexity::{ComplexitySummary, FileComplexity, FunctionComplexity, FunctionOffender, LanguageComplexity}, count::LanguageStats, diff::diff, history::HistoryPoint, policy::Violation, report::Report};
+
+    fn state()->HashMap<String, LanguageStats>{
+        let mut state = HashMap::new();
+        state.insert("Rust".to_string(), LanguageStats{ files: 2, code: 30, comment: 5, blank: 5 });
+        state.insert("Go".to_string(), LanguageStats{ files: 1, code: 10, comment: 0, blank: 0 });
+        state.insert("C".to_string(), LanguageStats{ files: 1, code: 10, comment: 0, blank: 0 });
+        state
+    }
+
+    #[test]
+    fn test_csv(){
+        let out = render(&state(), OutputFormat::Csv, SortKey::Code).unwrap();
+        assert_eq!("language,files,lines,code,comment,blank,code_percent,lines_percent
+Rust,2,40,30,5,5,60.00,66.67
+C,1,10,10,0,0,20.00,16.67
+Go,1,10,10,0,0,20.00,16.67
+Total,4,60,50,5,5,100.00,100.00
+", out);
+    }
+
+    #[test]
+    fn test_json(){
+        let out = render(&state(), OutputFormat::Json, SortKey::Code).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!("Rust", value["languages"][0]["language"]);
+        assert_eq!(66.67, value["languages"][0]["lines_percent"]);
+        assert_eq!(60, value["total"]["lines"]);
+    }
+
+    #[test]
+    fn test_markdown_and_table(){
+        let out = render(&state(), OutputFormat::Markdown, SortKey::Code).unwrap();
+        assert!(out.contains("| Rust | 2 | 40 | 30 | 5 | 5 | 60.00% | 66.67% |"));
+        let out = render(&state(), OutputFormat::Table, SortKey::Code).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(7, lines.len());
+        assert!(lines[2].starts_with("Rust  "));
+        assert!(lines[6].starts_with("Total "));
+        let out = render(&HashMap::new(), OutputFormat::Text, SortKey::Code).unwrap();
+        assert_eq!("未统计到代码\n", out);
+        let out = render(&state(), OutputFormat::Csv, SortKey::Name).unwrap();
+        assert!(out.lines().nth(1).unwrap().starts_with("C,"));
+    }
+
+    #[test]
+    fn test_breakdown(){
+        let file = FileStats{ path: PathBuf::from("src/main.rs"), root: PathBuf::from("src"), language: "Rust".to_string(), stats: LanguageStats{ files: 1, code: 8, comment: 1, blank: 1 }, ..Default::default() };
+        let out = render_files(&[&file], OutputFormat::Csv).unwrap();
+        assert_eq!("path,language,lines,code,comment,blank\nsrc/main.rs,Rust,10,8,1,1\n", out);
+        let out = render_files(&[&file], OutputFormat::Text).unwrap();
+        assert_eq!("src/main.rs（Rust）有10行，代码8行，注释1行，空行1行\n", out);
+        let dir = DirStats{
+            path: PathBuf::new(),
+            total: LanguageStats{ files: 3, code: 12, comment: 1, blank: 1 },
+            languages: vec![("Rust".to_string(), LanguageStats{ files: 1, code: 8, comment: 1, blank: 1 }), ("TOML".to_string(), LanguageStats{ files: 2, code: 4, comment: 0, blank: 0 })],
+        };
+        let out = render_directories(std::slice::from_ref(&dir), OutputFormat::Text).unwrap();
+        assert_eq!(".有14行，代码12行：Rust 8行，TOML 4行\n", out);
+        let out = render_directories(std::slice::from_ref(&dir), OutputFormat::Table).unwrap();
+        assert_eq!(5, out.lines().count());
+        assert!(out.lines().nth(3).unwrap().starts_with(".     Rust "));
+        let out = render_directories(&[dir], OutputFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(12, value["directories"][0]["total"]["code"]);
+        assert_eq!("TOML", value["directories"][0]["languages"][1]["language"]);
+    }
+
+    #[test]
+    fn test_diff(){
+        let file = |root: &str, path: &str, code: u64| FileStats{ path: PathBuf::from(root).join(path), root: PathBuf::from(root), language: "Rust".to_string(), stats: LanguageStats{ files: 1, code, comment: 0, blank: 0 }, ..Default::default() };
+        let old = Report{ files: vec![file("a", "main.rs", 10), file("a", "old.rs", 5)], skipped: Vec::new() };
+        let new = Report{ files: vec![file("b", "main.rs", 12)], skipped: Vec::new() };
+        let out = render_diff(&diff(&old, &new), OutputFormat::Text).unwrap();
+        assert_eq!("Rust：15 → 12行（-3），占比 100.00% → 100.00%（+0.00个百分点）
+合计：15 → 12行（-3），代码15 → 12行（-3）
+删除文件：
+  - old.rs（Rust）5行
+修改文件：
+  ~ main.rs（Rust）10 → 12行（+2），代码+2，注释+0，空行+0
+", out);
+        let out = render_diff(&diff(&old, &new), OutputFormat::Csv).unwrap();
+        assert_eq!("type,path,language,old_lines,new_lines,lines,code,comment,blank,old_percent,new_percent,percent
+language,,Rust,15,12,-3,-3,0,0,100.00,100.00,0.00
+total,,Total,15,12,-3,-3,0,0,100.00,100.00,0.00
+changed,main.rs,Rust,10,12,2,2,0,0,,,
+removed,old.rs,Rust,5,0,-5,-5,0,0,,,
+", out);
+    }
+
+    #[test]
+    fn test_history(){
+        let point = HistoryPoint{
+            commit: "0123456789abcdef".to_string(),
+            tree: "fedcba".to_string(),
+            time: 0,
+            date: "1970-01-01".to_string(),
+            languages: [("Rust".to_string(), LanguageStats{ files: 1, code: 3, comment: 0, blank: 1 }), ("C".to_string(), LanguageStats{ files: 2, code: 5, comment: 1, blank: 0 })].into_iter().collect(),
+        };
+        let out = render_history(std::slice::from_ref(&point), OutputFormat::Text).unwrap();
+        assert_eq!("1970-01-01 0123456：C 5行，Rust 3行\n", out);
+        let out = render_history(&[point], OutputFormat::Csv).unwrap();
+        assert_eq!("date,commit,language,files,lines,code,comment,blank
+1970-01-01,0123456789abcdef,C,2,6,5,1,0
+1970-01-01,0123456789abcdef,Rust,1,4,3,0,1
+", out);
+    }
+
+    #[test]
+    fn test_blame(){
+        let stats = LanguageStats{ files: 1, code: 3, comment: 1, blank: 0 };
+        let author = AuthorStats{
+            author: "Alice <alice@corp.com>".to_string(),
+            total: stats,
+            languages: [("Rust".to_string(), stats)].into_iter().collect(),
+            directories: [(PathBuf::from("src"), stats)].into_iter().collect(),
+        };
+        let out = render_blame(std::slice::from_ref(&author), OutputFormat::Text).unwrap();
+        assert_eq!("Alice <alice@corp.com>：代码3行，注释1行，空行0行\n  语言：Rust 3行\n  文件夹：src 3行\n", out);
+        let out = render_blame(&[author], OutputFormat::Csv).unwrap();
+        assert_eq!("author,kind,name,files,lines,code,comment,blank
+Alice <alice@corp.com>,total,,1,4,3,1,0
+Alice <alice@corp.com>,language,Rust,1,4,3,1,0
+Alice <alice@corp.com>,directory,src,1,4,3,1,0
+", out);
+    }
+
+    #[test]
+    fn test_violations(){
+        assert_eq!("检查2条规则，全部通过\n", render_violations(&[], 2, OutputFormat::Text).unwrap());
+        let violations = [
+            Violation{ rule: "Python".to_string(), path: None, message: "占比36.59%，超过30%".to_string() },
+            Violation{ rule: "新文件".to_string(), path: Some(PathBuf::from("src/gen.rs")), message: "2100行，超过2000行".to_string() },
+        ];
+        let out = render_violations(&violations, 2, OutputFormat::Text).unwrap();
+        assert_eq!("检查2条规则，发现2处违规：\n  [Python] 占比36.59%，超过30%\n  [新文件] src/gen.rs：2100行，超过2000行\n", out);
+        let out = render_violations(&violations, 2, OutputFormat::Csv).unwrap();
+        assert_eq!("rule,path,message\nPython,,占比36.59%，超过30%\n新文件,src/gen.rs,2100行，超过2000行\n", out);
+    }
+
+    #[test]
+    fn test_complexity(){
+        assert_eq!("没有可以计算复杂度的文件\n", render_complexity(&ComplexitySummary::default(), OutputFormat::Text).unwrap());
+        let summary = ComplexitySummary{
+            languages: vec![LanguageComplexity{
+                language: "Rust".to_string(), files: 2, functions: 3, cyclomatic: 14, nesting: 5, max_cyclomatic: 10, max_nesting: 4,
+                avg_functions: 1.5, avg_cyclomatic: 14.0 / 3.0, avg_nesting: 5.0 / 3.0,
+            }],
+            files: vec![FileComplexity{ path: PathBuf::from("src/count.rs"), language: "Rust".to_string(), functions: 2, cyclomatic: 12, max_cyclomatic: 10, max_nesting: 4 }],
+            functions: vec![FunctionOffender{
+                path: PathBuf::from("src/count.rs"), language: "Rust".to_string(),
+                function: FunctionComplexity{ name: "line".to_string(), line: 82, cyclomatic: 10, nesting: 4 },
+            }],
+        };
+        let out = render_complexity(&summary, OutputFormat::Text).unwrap();
+        assert_eq!("Rust：2个文件，3个函数，平均每个文件1.50个函数，每个函数圈复杂度4.67（最大10），嵌套1.67层（最大4层）\n圈复杂度最高的1个文件：\n  src/count.rs（Rust）：2个函数，圈复杂度合计12（最大10），嵌套最大4层\n圈复杂度最高的1个函数：\n  src/count.rs:82 line（Rust）：圈复杂度10，嵌套4层\n", out);
+        let out = render_complexity(&summary, OutputFormat::Csv).unwrap();
+        assert_eq!("kind,language,path,function,line,files,functions,cyclomatic,nesting\nlanguage,Rust,,,,2,3,4.67,1.67\nfile,Rust,src/count.rs,,,,2,12,4\nfunction,Rust,src/count.rs,line,82,,,10,4\n", out);
+        let json: serde_json::Value = serde_json::from_str(&render_complexity(&summary, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(82, json["functions"][0]["line"]);
+        assert_eq!(12, json["files"][0]["cyclomatic"]);
+        assert_eq!(10, json["languages"][0]["max_cyclomatic"]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::HashSet, fmt::Display, fs, path::{Path, PathBuf}};
+use anyhow::{bail, Context, Ok, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{breakdown::FileStats, count::LanguageStats, output::percent, report::Report};
+
+/// # Policy
+/// CI中检查的规则，从toml或json文件读取
+/// * baseline: 用于比较的基线，即`--save`保存的json文件，相对路径相对于规则文件所在的文件夹
+/// * rule: 规则列表
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy{
+    #[serde(default)]
+    pub baseline: Option<PathBuf>,
+    #[serde(default)]
+    pub rule: Vec<Rule>,
+}
+
+/// # Rule
+/// 一条规则，先用path、language、new_files选出文件，再检查各项限制，未设置的限制不检查
+/// * name: 规则名称，输出违规时使用，默认根据内容生成
+/// * path: 只检查该路径（相对于统计的根目录）下的文件
+/// * language: 只检查该语言（生成的代码与第三方代码为`Generated/Vendored`，嵌入的代码为`Python (embedded)`、测试代码为`Rust (test)`这样的名称），max_share、max_share_increase为该语言在所选文件中的占比
+/// * new_files: 只检查基线中不存在的文件
+/// * max_share: 语言的总行数占所选文件的最大百分比
+/// * max_share_increase: 语言占比相对基线最多增加的百分点
+/// * max_file_lines: 单个文件的最大总行数
+/// * min_comment_ratio: 注释行占代码与注释行的最小百分比
+/// * max_code: 代码行的最大值
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule{
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub new_files: bool,
+    #[serde(default)]
+    pub max_share: Option<f64>,
+    #[serde(default)]
+    pub max_share_increase: Option<f64>,
+    #[serde(default)]
+    pub max_file_lines: Option<u64>,
+    #[serde(default)]
+    pub min_comment_ratio: Option<f64>,
+    #[serde(default)]
+    pub max_code: Option<u64>,
+}
+
+/// # Violation
+/// 违反规则的一项
+/// * rule: 规则名称
+/// * path: 违规的文件，针对整体的限制为None
+/// * message: 说明
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation{
+    pub rule: String,
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Policy {
+    /// 读取规则文件，根据后缀判断toml或json格式，baseline的相对路径转换为相对于规则文件所在的文件夹
+    pub fn load(path: &Path)->Result<Policy>{
+        let format = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+        let content = fs::read_to_string(path).with_context(|| format!("无法读取规则文件{}", path.display()))?;
+        let mut policy = Policy::parse(&content, &format).with_context(|| format!("规则文件{}格式错误", path.display()))?;
+        if let (Some(baseline), Some(dir)) = (&policy.baseline, path.parent()){
+            policy.baseline = Some(dir.join(baseline));
+        }
+        Ok(policy)
+    }
+
+    /// 解析toml或json格式的规则
+    pub fn parse(content: &str, format: &str)->Result<Policy>{
+        let policy: Policy = match format {
+            "toml" => toml::from_str(content)?,
+            "json" => serde_json::from_str(content)?,
+            _ => bail!("规则文件只支持toml与json格式"),
+        };
+        for rule in policy.rule.iter(){
+            if (rule.max_share.is_some() || rule.max_share_increase.is_some()) && rule.language.is_none(){
+                bail!("规则{}：max_share、max_share_increase需要指定language", rule);
+            }
+        }
+        Ok(policy)
+    }
+
+    /// 检查统计结果，返回所有违规，需要基线的规则没有基线时返回错误
+    pub fn check(&self, report: &Report, baseline: Option<&Report>)->Result<Vec<Violation>>{
+        let mut violations = Vec::new();
+        for rule in self.rule.iter(){
+            violations.extend(rule.check(report, baseline)?);
+        }
+        Ok(violations)
+    }
+}
+
+impl Rule {
+    /// 检查一条规则
+    pub fn check(&self, report: &Report, baseline: Option<&Report>)->Result<Vec<Violation>>{
+        let needs_baseline = self.new_files || self.max_share_increase.is_some();
+        let baseline = match baseline {
+            None if needs_baseline => bail!("规则{}需要基线，请在规则文件中设置baseline或使用--baseline", self),
+            baseline => baseline,
+        };
+        let old_paths = baseline.map(|b| b.files.iter().map(FileStats::relative_path).collect::<HashSet<_>>()).unwrap_or_default();
+        let files = report.files.iter()
+            .filter(|f| self.in_scope(f))
+            .filter(|f| !self.new_files || !old_paths.contains(f.relative_path()))
+            .collect::<Vec<_>>();
+        let mut violations = Vec::new();
+        let violation = |path: Option<&Path>, message: String| Violation{ rule: self.to_string(), path: path.map(Path::to_path_buf), message };
+
+        let (total, selected) = self.sum(&files);
+        if let Some(max) = self.max_share{
+            let share = percent(selected.lines(), total.lines());
+            if share > max{
+                violations.push(violation(None, format!("占比{:.2}%，超过{}%", share, max)));
+            }
+        }
+        if let (Some(max), Some(baseline)) = (self.max_share_increase, baseline){
+            let old_files = baseline.files.iter().filter(|f| self.in_scope(f)).collect::<Vec<_>>();
+            let (old_total, old_selected) = self.sum(&old_files);
+            let (old, new) = (percent(old_selected.lines(), old_total.lines()), percent(selected.lines(), total.lines()));
+            let increase = ((new - old) * 100.0).round() / 100.0;
+            if increase > max{
+                violations.push(violation(None, format!("占比{:.2}% → {:.2}%，增加{:.2}个百分点，超过{}个百分点", old, new, increase, max)));
+            }
+        }
+        if let Some(max) = self.max_file_lines{
+            for file in files.iter().filter(|f| f.parts().iter().any(|(name, _)| self.matches_language(name)) && f.total().lines() > max){
+                violations.push(violation(Some(&file.path), format!("{}行，超过{}行", file.total().lines(), max)));
+            }
+        }
+        if let Some(min) = self.min_comment_ratio{
+            let ratio = percent(selected.comment, selected.code + selected.comment);
+            if selected.code + selected.comment > 0 && ratio < min{
+                violations.push(violation(None, format!("注释占比{:.2}%，低于{}%", ratio, min)));
+            }
+        }
+        if let Some(max) = self.max_code{
+            if selected.code > max{
+                violations.push(violation(None, format!("代码{}行，超过{}行", selected.code, max)));
+            }
+        }
+        Ok(violations)
+    }
+
+    /// 文件是否在path下，language只在计算占比时区分
+    fn in_scope(&self, file: &FileStats)->bool{
+        self.path.as_ref().is_none_or(|path| file.relative_path().starts_with(path))
+    }
+
+    /// name为按语言汇总时的名称，见`FileStats::parts`
+    fn matches_language(&self, name: &str)->bool{
+        self.language.as_ref().is_none_or(|language| language.eq_ignore_ascii_case(name))
+    }
+
+    /// 所选文件的合计以及其中符合language的合计
+    fn sum(&self, files: &[&FileStats])->(LanguageStats, LanguageStats){
+        let (mut total, mut selected) = (LanguageStats::default(), LanguageStats::default());
+        for file in files.iter(){
+            total += file.total();
+            for (name, stats) in file.parts(){
+                if self.matches_language(&name){
+                    selected += stats;
+                }
+            }
+        }
+        (total, selected)
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name{
+            return write!(f, "{}", name);
+        }
+        let mut parts = Vec::new();
+        if let Some(language) = &self.language{
+            parts.push(language.clone());
+        }
+        if let Some(path) = &self.path{
+            parts.push(path.display().to_string());
+        }
+        if self.new_files{
+            parts.push("新文件".to_string());
+        }
+        match parts.is_empty() {
+            true => write!(f, "全部文件"),
+            false => write!(f, "{}", parts.join(" ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::PathBuf;
+
+    use super::Policy;
+    use crate::{breakdown::FileStats, count::LanguageStats, report::Report};
+
+    fn report(files: &[(&str, &str, u64, u64)])->Report{
+        let files = files.iter().map(|(path, language, code, comment)| FileStats{
+            path: PathBuf::from("repo").join(path),
+            root: PathBuf::from("repo"),
+            language: language.to_string(),
+            stats: LanguageStats{ files: 1, code: *code, comment: *comment, blank: 0 },
+            ..Default::default()
+        }).collect();
+        Report{ files, skipped: Vec::new() }
+    }
+
+    #[test]
+    fn test_check(){
+        let policy = Policy::parse(r#"
+            [[rule]]
+            name = "Python占比"
+            language = "python"
+            max_share = 30
+            max_share_increase = 5
+
+            [[rule]]
+            new_files = true
+            max_file_lines = 2000
+
+            [[rule]]
+            path = "src"
+            min_comment_ratio = 10
+        "#, "toml").unwrap();
+        let old = report(&[("src/main.rs", "Rust", 2500, 500), ("build.py", "Python", 500, 0)]);
+        let new = report(&[("src/main.rs", "Rust", 2600, 500), ("build.py", "Python", 3000, 0), ("src/gen.rs", "Rust", 2100, 0)]);
+        let violations = policy.check(&new, Some(&old)).unwrap();
+        let summary = violations.iter().map(|v| (v.rule.as_str(), v.path.clone())).collect::<Vec<_>>();
+        assert_eq!(vec![("Python占比", None), ("Python占比", None), ("新文件", Some(PathBuf::from("repo/src/gen.rs"))), ("src", None)], summary);
+        let messages = violations.iter().map(|v| v.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["占比36.59%，超过30%", "占比14.29% → 36.59%，增加22.30个百分点，超过5个百分点", "2100行，超过2000行", "注释占比9.62%，低于10%"], messages);
+        assert!(policy.check(&new, None).is_err());
+        assert!(policy.check(&old, Some(&old)).unwrap().is_empty());
+        assert!(Policy::parse("[[rule]]\nmax_share = 30\n", "toml").is_err());
+        assert!(Policy::parse("[[rule]]\nmax_lines = 30\n", "toml").is_err());
+    }
+}
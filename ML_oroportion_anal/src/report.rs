@@ -0,0 +1,54 @@
+use std::{collections::HashMap, fs, path::Path};
+use anyhow::{Context, Ok, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{breakdown::{by_directory, by_language, top_files, DirStats, FileStats}, count::{LanguageStats, SkippedFile}, output::SortKey};
+
+/// # Report
+/// `Counter::count`的统计结果
+/// * files: 每个文件的统计结果，按路径排序
+/// * skipped: 没有统计的文件及原因，按路径排序
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report{
+    pub files: Vec<FileStats>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl Report {
+    /// 保存为json文件，之后可以用`load`读取并比较
+    pub fn save(&self, path: &Path)->Result<()>{
+        fs::write(path, serde_json::to_string_pretty(self)?).with_context(|| format!("无法写入文件{}", path.display()))?;
+        Ok(())
+    }
+
+    /// 读取`save`保存的json文件
+    pub fn load(path: &Path)->Result<Report>{
+        let content = fs::read_to_string(path).with_context(|| format!("无法读取文件{}", path.display()))?;
+        let report = serde_json::from_str(&content).with_context(|| format!("无法解析统计结果{}", path.display()))?;
+        Ok(report)
+    }
+
+    /// 按语言汇总
+    pub fn languages(&self)->HashMap<String, LanguageStats>{
+        by_language(&self.files)
+    }
+
+    /// 所有文件的合计
+    pub fn total(&self)->LanguageStats{
+        let mut total = LanguageStats::default();
+        for file in self.files.iter(){
+            total += file.total();
+        }
+        total
+    }
+
+    /// 按排序方式输出前top个文件
+    pub fn top_files(&self, sort: SortKey, top: Option<usize>)->Vec<&FileStats>{
+        top_files(&self.files, sort, top)
+    }
+
+    /// 按根目录下depth层的文件夹汇总
+    pub fn directories(&self, depth: usize, sort: SortKey, top: Option<usize>)->Vec<DirStats>{
+        by_directory(&self.files, depth, sort, top)
+    }
+}
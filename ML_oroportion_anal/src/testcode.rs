@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use crate::{count::{LanguageStats, Scanned}, lang::Language};
+
+/// 测试代码按语言汇总时使用的名称，例如`Rust (test)`
+pub fn test_name(language: &str)->String{
+    format!("{} (test)", language)
+}
+
+/// 其中的文件都是测试代码的文件夹
+const TEST_DIRS: [&str; 3] = ["tests", "test", "__tests__"];
+
+/// 测试文件名的后缀，另外`test_*.py`也是测试文件
+const TEST_SUFFIXES: [&str; 4] = ["_test.go", "_test.py", "Test.java", "Tests.java"];
+
+/// 根据相对于根目录的路径判断整个文件是否为测试代码
+pub(crate) fn is_test_path(relative: &Path)->bool{
+    let name = relative.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let in_test_dir = relative.parent().is_some_and(|p| p.iter().any(|d| d.to_str().is_some_and(|d| TEST_DIRS.contains(&d))));
+    in_test_dir || TEST_SUFFIXES.iter().any(|s| name.ends_with(s)) || (name.starts_with("test_") && name.ends_with(".py"))
+}
+
+/// 文件中属于测试代码的行数：测试文件整个文件都是测试代码，否则为`#[cfg(test)]`标记的部分
+pub(crate) fn test_stats(relative: &Path, scanned: &Scanned)->LanguageStats{
+    match is_test_path(relative) {
+        true => scanned.stats,
+        false => scanned.test,
+    }
+}
+
+/// `TestRegions`的状态，Attribute为已经出现`#[cfg(test)]`，还没有遇到`{`或`;`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RegionState{
+    #[default]
+    Outside,
+    Attribute,
+    Inside(usize),
+}
+
+/// # TestRegions
+/// 逐行识别rust中`#[cfg(test)]`标记的模块（以及函数、impl等其他项），
+/// 从属性所在的行开始，到对应的`}`（或者`mod test;`这样的`;`）为止
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TestRegions{
+    state: RegionState,
+}
+
+impl TestRegions {
+    /// 支持识别测试代码区域的语言
+    pub(crate) fn supports(language: &Language)->bool{
+        language.name == "Rust"
+    }
+
+    /// 处理一行去掉注释与字符串后的代码（`LineCounter::code`），属于测试代码时返回true
+    pub(crate) fn line(&mut self, code: &[u8])->bool{
+        const ATTRIBUTE: &[u8] = b"#[cfg(test)]";
+        let code = code.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect::<Vec<_>>();
+        let start = match self.state {
+            RegionState::Outside => match code.windows(ATTRIBUTE.len()).position(|w| w == ATTRIBUTE) {
+                Some(position) => {
+                    self.state = RegionState::Attribute;
+                    position + ATTRIBUTE.len()
+                },
+                None => return false,
+            },
+            _ => 0,
+        };
+        for b in code[start..].iter(){
+            self.state = match (self.state, b) {
+                (RegionState::Attribute, b'{') => RegionState::Inside(1),
+                (RegionState::Attribute, b';') | (RegionState::Inside(1), b'}') => RegionState::Outside,
+                (RegionState::Inside(depth), b'{') => RegionState::Inside(depth + 1),
+                (RegionState::Inside(depth), b'}') => RegionState::Inside(depth - 1),
+                (state, _) => state,
+            };
+            if self.state == RegionState::Outside{
+                break;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::Path;
+
+    use super::{is_test_path, test_stats, TestRegions};
+    use crate::{count::{scan_reader, LanguageStats, LineCounter}, lang::Languages};
+
+    #[test]
+    fn test_paths(){
+        for path in ["tests/cli.rs", "crates/foo/tests/common/mod.rs", "src/test/java/AppTest.java", "pkg/server_test.go", "test_api.py", "lib/api_test.py", "src/AppTests.java", "web/__tests__/app.js"]{
+            assert!(is_test_path(Path::new(path)), "{}", path);
+        }
+        for path in ["src/main.rs", "tests.rs", "pkg/server.go", "contest.py", "src/Testing.java", "latest/a.rs"]{
+            assert!(!is_test_path(Path::new(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_regions(){
+        let languages = Languages::builtin();
+        let rust = languages.find_tag("rust").unwrap();
+        let content = r##"fn main(){}
+// #[cfg(test)] 注释中的不算
+const S: &str = "#[cfg(test)] {";
+
+#[cfg(test)]
+mod test{
+    const BRACE: char = '}';
+    #[test]
+    fn test_main(){
+        let s = "}}";
+    }
+}
+fn after(){}
+#[cfg(test)] mod bench;
+#[cfg(test)] fn helper()->u8 { 1 }
+fn last(){
+}
+"##;
+        let mut counter = LineCounter::new(rust).keep_code();
+        let mut regions = TestRegions::default();
+        let tests = content.lines().enumerate().filter(|(_, line)| {
+            counter.line(line.as_bytes());
+            regions.line(counter.code())
+        }).map(|(index, _)| index + 1).collect::<Vec<_>>();
+        assert_eq!(vec![5, 6, 7, 8, 9, 10, 11, 12, 14, 15], tests);
+    }
+
+    #[test]
+    fn test_scanned(){
+        let languages = Languages::builtin();
+        let scan = |tag: &str, content: &str| scan_reader(content.as_bytes(), languages.find_tag(tag).unwrap(), Some(&languages), false).unwrap();
+        let lib = scan("rust", "pub fn add(a: u8, b: u8)->u8{ a + b }\n\n#[cfg(test)]\nmod test{\n    // 测试\n    #[test]\n    fn test_add(){}\n}\n");
+        assert_eq!(LanguageStats{ files: 1, code: 5, comment: 1, blank: 0 }, test_stats(Path::new("src/lib.rs"), &lib));
+        assert_eq!(LanguageStats{ files: 1, code: 6, comment: 1, blank: 1 }, test_stats(Path::new("tests/lib.rs"), &lib));
+        let go = scan("go", "package pkg\n\nimport \"testing\"\n");
+        assert!(test_stats(Path::new("pkg/add.go"), &go).is_empty());
+        assert_eq!(go.stats, test_stats(Path::new("pkg/add_test.go"), &go));
+    }
+}
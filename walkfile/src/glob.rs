@@ -0,0 +1,173 @@
+use std::{fmt, path::{Component, Path}};
+
+/// # Glob
+/// gitignore风格的通配符
+/// * `*`: 匹配除`/`以外的任意字符
+/// * `?`: 匹配除`/`以外的单个字符
+/// * `**`: 匹配任意层级的文件夹，例如`**/test`、`src/**`、`a/**/b`
+/// * `[abc]`、`[a-z]`、`[!a]`: 字符集合
+/// * 以`/`结尾时只匹配文件夹
+/// * 不包含`/`（结尾的除外）时匹配任意层级的名称，否则匹配相对于根目录的完整路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob{
+    pattern: Vec<char>,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str)->Glob{
+        let mut pattern = pattern.trim();
+        let dir_only = pattern.ends_with('/');
+        pattern = pattern.trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        pattern = pattern.strip_prefix("./").unwrap_or(pattern).trim_start_matches('/');
+        Glob{
+            pattern: pattern.chars().collect(),
+            anchored,
+            dir_only,
+        }
+    }
+
+    /// 判断相对于根目录的路径是否匹配
+    /// ```rust
+    /// use std::path::Path;
+    /// use walkfile::Glob;
+    /// assert!(Glob::new("*.min.js").matches(Path::new("static/app.min.js"), false));
+    /// assert!(Glob::new("target/").matches(Path::new("crates/a/target"), true));
+    /// assert!(!Glob::new("/target").matches(Path::new("crates/a/target"), true));
+    /// assert!(Glob::new("src/**/*.rs").matches(Path::new("src/a/b/c.rs"), false));
+    /// ```
+    pub fn matches(&self, relative: &Path, is_dir: bool)->bool{
+        if self.dir_only && !is_dir{
+            return false;
+        }
+        let parts = relative.components().filter_map(|c| match c {
+            Component::Normal(p) => p.to_str(),
+            _ => None,
+        }).collect::<Vec<_>>();
+        if self.anchored{
+            let text = parts.join("/").chars().collect::<Vec<_>>();
+            return match_from(&self.pattern, &text);
+        }
+        match parts.last() {
+            None => false,
+            Some(name) => match_from(&self.pattern, &name.chars().collect::<Vec<_>>()),
+        }
+    }
+}
+
+/// 规范化后的通配符，`Glob::new`重新解析后得到相同的结果
+/// ```rust
+/// use walkfile::Glob;
+/// assert_eq!("/src/*.rs", Glob::new("./src/*.rs").to_string());
+/// assert_eq!("target/", Glob::new("target/").to_string());
+/// ```
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pattern = self.pattern.iter().collect::<String>();
+        write!(f, "{}{}{}", if self.anchored { "/" } else { "" }, pattern, if self.dir_only { "/" } else { "" })
+    }
+}
+
+fn match_from(p: &[char], t: &[char])->bool{
+    if p.is_empty(){
+        return t.is_empty();
+    }
+    match p[0] {
+        '*' if p.get(1) == Some(&'*') => {
+            let rest = &p[2..];
+            if rest.is_empty(){
+                return true;
+            }
+            if rest[0] == '/'{
+                // `**/`可以匹配零个或多个文件夹
+                let rest = &rest[1..];
+                return match_from(rest, t) || (0..t.len()).any(|i| t[i] == '/' && match_from(rest, &t[i + 1..]));
+            }
+            (0..=t.len()).any(|i| match_from(rest, &t[i..]))
+        },
+        '*' => {
+            let rest = &p[1..];
+            for i in 0..=t.len(){
+                if match_from(rest, &t[i..]){
+                    return true;
+                }
+                if i < t.len() && t[i] == '/'{
+                    break;
+                }
+            }
+            false
+        },
+        '?' => !t.is_empty() && t[0] != '/' && match_from(&p[1..], &t[1..]),
+        '[' => match match_class(&p[1..], t.first().copied()) {
+            // 没有闭合的`[`按普通字符处理
+            None => !t.is_empty() && t[0] == '[' && match_from(&p[1..], &t[1..]),
+            Some((matched, len)) => matched && match_from(&p[1 + len..], &t[1..]),
+        },
+        '\\' if p.len() > 1 => !t.is_empty() && t[0] == p[1] && match_from(&p[2..], &t[1..]),
+        c => !t.is_empty() && t[0] == c && match_from(&p[1..], &t[1..]),
+    }
+}
+
+/// 匹配`[`之后的字符集合，返回是否匹配以及集合占用的长度（包含`]`）
+fn match_class(p: &[char], c: Option<char>)->Option<(bool, usize)>{
+    let mut i = 0;
+    let negate = matches!(p.first(), Some('!') | Some('^'));
+    if negate{
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < p.len(){
+        if p[i] == ']' && !first{
+            let matched = match c {
+                None | Some('/') => false,
+                Some(_) => matched != negate,
+            };
+            return Some((matched, i + 1));
+        }
+        first = false;
+        if i + 2 < p.len() && p[i + 1] == '-' && p[i + 2] != ']'{
+            if let Some(c) = c{
+                matched |= p[i] <= c && c <= p[i + 2];
+            }
+            i += 3;
+        }else{
+            matched |= c == Some(p[i]);
+            i += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::Path;
+
+    use super::Glob;
+
+    fn matches(pattern: &str, path: &str)->bool{
+        Glob::new(pattern).matches(Path::new(path), false)
+    }
+
+    #[test]
+    fn test_glob(){
+        assert!(matches("*.rs", "src/main.rs"));
+        assert!(!matches("*.rs", "src/main.rsx"));
+        assert!(matches("ma?n.rs", "main.rs"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("**/vendor", "a/b/vendor"));
+        assert!(matches("**/vendor", "vendor"));
+        assert!(matches("docs/**", "docs/a/b.md"));
+        assert!(matches("file[0-9].txt", "file3.txt"));
+        assert!(!matches("file[!0-9].txt", "file3.txt"));
+        assert!(matches("\\*.txt", "*.txt"));
+        assert!(!matches("\\*.txt", "a.txt"));
+        assert!(!Glob::new("build/").matches(Path::new("build"), false));
+        assert!(Glob::new("build/").matches(Path::new("build"), true));
+        assert!(matches("./src", "src"));
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{Glob, WalkOptions};
+
+/// 默认读取的忽略文件
+pub const DEFAULT_IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// # IgnoreRule
+/// 忽略文件中的一条规则
+/// * glob: 匹配的通配符
+/// * negate: 以`!`开头的规则，重新包含之前被忽略的条目
+/// * base: 忽略文件所在的文件夹，相对于仓库的根目录（不在git仓库中时为遍历的根目录）
+#[derive(Debug, Clone)]
+pub struct IgnoreRule{
+    pub glob: Glob,
+    pub negate: bool,
+    pub base: PathBuf,
+}
+
+/// 解析忽略文件的内容，支持`#`注释、`!`取反以及`\#`、`\!`转义
+pub fn parse_ignore(content: &str, base: &Path)->Vec<IgnoreRule>{
+    let mut rules = Vec::new();
+    for line in content.lines(){
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#'){
+            continue;
+        }
+        let (negate, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+        let pattern = pattern.strip_prefix('\\').filter(|p| p.starts_with(['#', '!'])).unwrap_or(pattern);
+        rules.push(IgnoreRule{ glob: Glob::new(pattern), negate, base: base.to_path_buf() });
+    }
+    rules
+}
+
+/// # DirFilter
+/// 遍历一个文件夹时使用的过滤条件，包含从仓库根目录到当前文件夹的所有忽略规则
+/// * prefix: 遍历的根目录相对于仓库根目录的路径
+/// * rules: 忽略规则，base相对于仓库根目录
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DirFilter{
+    prefix: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl DirFilter {
+    /// 开始遍历根目录root时的过滤条件：root在git仓库中时，先读取仓库根目录到root之间（不含root）每一层的忽略文件
+    pub(crate) fn new(root: &Path, options: &WalkOptions)->DirFilter{
+        let mut filter = DirFilter::default();
+        if options.ignore_files.is_empty(){
+            return filter;
+        }
+        let root = match fs::canonicalize(root) {
+            Err(_) => return filter,
+            std::result::Result::Ok(root) => root,
+        };
+        let top = match root.ancestors().find(|dir| dir.join(".git").exists()) {
+            None => return filter,
+            Some(top) => top,
+        };
+        filter.prefix = root.strip_prefix(top).unwrap_or(Path::new("")).to_path_buf();
+        let mut ancestors = root.ancestors().skip(1).take_while(|dir| dir.starts_with(top)).collect::<Vec<_>>();
+        ancestors.reverse();
+        for dir in ancestors{
+            filter.read(dir, dir.strip_prefix(top).unwrap_or(Path::new("")), options);
+        }
+        filter
+    }
+
+    /// 进入文件夹，读取其中的忽略文件，relative为文件夹相对于根目录的路径
+    pub(crate) fn enter(&self, dir: &Path, relative: &Path, options: &WalkOptions)->DirFilter{
+        let mut filter = self.clone();
+        filter.read(dir, &self.prefix.join(relative), options);
+        filter
+    }
+
+    /// 读取文件夹dir中的忽略文件，base为dir相对于仓库根目录的路径
+    fn read(&mut self, dir: &Path, base: &Path, options: &WalkOptions){
+        for name in options.ignore_files.iter(){
+            if let std::result::Result::Ok(content) = fs::read_to_string(dir.join(name)){
+                self.rules.extend(parse_ignore(&content, base));
+            }
+        }
+    }
+
+    /// 判断条目是否需要保留，relative为条目相对于根目录的路径
+    pub(crate) fn allows(&self, name: &str, relative: &Path, is_dir: bool, options: &WalkOptions)->bool{
+        if !options.hidden.allows(name){
+            return false;
+        }
+        // 使用忽略文件时和git一样总是跳过.git
+        if is_dir && name == ".git" && !options.ignore_files.is_empty(){
+            return false;
+        }
+        if options.excludes.iter().any(|e| e.matches(relative, is_dir)){
+            return false;
+        }
+        // 后面的规则优先
+        let relative = self.prefix.join(relative);
+        for rule in self.rules.iter().rev(){
+            let path = match relative.strip_prefix(&rule.base) {
+                Err(_) => continue,
+                std::result::Result::Ok(path) => path,
+            };
+            if rule.glob.matches(path, is_dir){
+                return rule.negate;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::{Path, PathBuf};
+
+    use super::{parse_ignore, DirFilter};
+    use crate::WalkOptions;
+
+    #[test]
+    fn test_rules(){
+        let mut filter = DirFilter{ prefix: PathBuf::new(), rules: parse_ignore("# 注释\n\ntarget/\n*.log\n!keep.log\n\\#file\n", Path::new("")) };
+        filter.rules.extend(parse_ignore("*.tmp\n/local\n", Path::new("sub")));
+        let options = WalkOptions::default();
+        let allows = |path: &str, is_dir: bool| {
+            let path = Path::new(path);
+            filter.allows(path.file_name().unwrap().to_str().unwrap(), path, is_dir, &options)
+        };
+        assert!(!allows("target", true));
+        assert!(allows("target", false));
+        assert!(!allows("a/debug.log", false));
+        assert!(allows("a/keep.log", false));
+        assert!(!allows("#file", false));
+        assert!(!allows("sub/x.tmp", false));
+        assert!(allows("x.tmp", false));
+        assert!(!allows("sub/local", true));
+        assert!(allows("sub/a/local", true));
+    }
+}
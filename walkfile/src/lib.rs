@@ -1,23 +1,58 @@
-use std::{fs, path::PathBuf, vec};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, vec};
 use anyhow::{Ok, Result};
 
+use ignore::DirFilter;
+
+mod glob;
+mod ignore;
+mod magic;
+mod policy;
+mod roots;
+mod visitor;
+
+pub use glob::Glob;
+pub use ignore::{parse_ignore, IgnoreRule, DEFAULT_IGNORE_FILES};
+pub use magic::{shebang_interpreter, sniff, sniff_file, ContentType, Magic, TextEncoding, SNIFF_LEN};
+pub use policy::{is_hidden, HiddenPolicy, SpecialFile, SpecialKind};
+pub use roots::{normalize_roots, walk_roots, walk_roots_each};
+use roots::Visited;
+pub use visitor::{walk_visit, Visitor, WalkControl};
+
 /// # WalfFileENtry
 /// * root： 根目录
 /// * child_dirs: root下所有的子文件夹名称
 /// * child_files: root下所有的文件
+/// * child_specials: root下既不是文件也不是文件夹的条目，例如socket、fifo、设备文件
+/// * walk_root: 调用`walk`时传入的根目录
+/// * child_contents: 开启`WalkOptions::sniff_content`时，child_files中每个文件按内容判断出的类型，读取失败的文件不会出现
 #[derive(Debug, Clone)]
 pub struct WalkFileEntry{
+    pub walk_root: PathBuf,
     pub root: PathBuf,
     pub child_dirs: Vec<String>,
     pub child_files: Vec<String>,
+    pub child_specials: Vec<SpecialFile>,
+    pub child_contents: HashMap<String, ContentType>,
+}
+
+/// # WalkOptions
+/// * hidden: 隐藏文件的处理策略
+/// * sniff_content: 是否读取文件开头的字节判断文件类型
+/// * excludes: 需要跳过的文件、文件夹，通配符匹配相对于根目录的路径
+/// * ignore_files: 需要遵守的忽略文件名称，例如`DEFAULT_IGNORE_FILES`中的`.gitignore`、`.ignore`，不为空时总是跳过`.git`文件夹
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions{
+    pub hidden: HiddenPolicy,
+    pub sniff_content: bool,
+    pub excludes: Vec<Glob>,
+    pub ignore_files: Vec<String>,
 }
 
 impl WalkFileEntry {
     /// 将属性以引用的方式构造成元组
     /// ```rust
     /// use std::{path::PathBuf, str::FromStr};
-    /// use crate::walk;
-    /// use anyhow::{Ok, Result};
+    /// use walkfile::walk;
     /// fn test_ref_tuple(){
     ///     let res = walk(&PathBuf::from_str("./").unwrap());
     ///     let res = match res {
@@ -35,8 +70,7 @@ impl WalkFileEntry {
     /// 将属性以克隆的方式构造成元组
     /// ```rust
     /// use std::{path::PathBuf, str::FromStr};
-    /// use crate::walk;
-    /// use anyhow::{Ok, Result};
+    /// use walkfile::walk;
     /// fn test_ref_tuple(){
     ///     let res = walk(&PathBuf::from_str("./").unwrap());
     ///     let res = match res {
@@ -53,6 +87,53 @@ impl WalkFileEntry {
 }
 
 
+impl WalkFileEntry {
+    /// root下所有文件的完整路径
+    pub fn file_paths(&self)->impl Iterator<Item = PathBuf> + '_{
+        self.child_files.iter().map(|name| self.root.join(name))
+    }
+
+    /// root下所有子文件夹的完整路径
+    pub fn dir_paths(&self)->impl Iterator<Item = PathBuf> + '_{
+        self.child_dirs.iter().map(|name| self.root.join(name))
+    }
+
+    /// 按内容判断出的文件类型，未开启`sniff_content`时总是None
+    pub fn content_type(&self, name: &str)->Option<&ContentType>{
+        self.child_contents.get(name)
+    }
+
+    /// root下按内容判断为文本的文件的完整路径
+    pub fn text_file_paths(&self)->impl Iterator<Item = PathBuf> + '_{
+        self.child_files.iter()
+            .filter(|name| self.content_type(name).map(|c| c.is_text()).unwrap_or(true))
+            .map(|name| self.root.join(name))
+    }
+
+    /// root相对于walk_root的路径，root就是walk_root时返回空路径
+    /// ```rust
+    /// use std::{path::{Path, PathBuf}, str::FromStr};
+    /// use walkfile::walk;
+    /// let res = walk(&PathBuf::from_str("./").unwrap()).unwrap();
+    /// assert!(res.iter().any(|e| e.relative_root() == Path::new("src")));
+    /// ```
+    pub fn relative_root(&self)->&Path{
+        self.root.strip_prefix(&self.walk_root).unwrap_or(&self.root)
+    }
+
+    /// root下所有文件相对于walk_root的路径
+    pub fn relative_file_paths(&self)->impl Iterator<Item = PathBuf> + '_{
+        let relative = self.relative_root();
+        self.child_files.iter().map(move |name| relative.join(name))
+    }
+
+    /// root下所有子文件夹相对于walk_root的路径
+    pub fn relative_dir_paths(&self)->impl Iterator<Item = PathBuf> + '_{
+        let relative = self.relative_root();
+        self.child_dirs.iter().map(move |name| relative.join(name))
+    }
+}
+
 /// 为WalkFileEntry 实现disply
 impl std::fmt::Display for WalkFileEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -63,8 +144,7 @@ impl std::fmt::Display for WalkFileEntry {
 /// 遍历指定根目录的入口函数
 /// ```rust
 ///use std::{path::PathBuf, str::FromStr};
-/// use crate::walk;
-/// use anyhow::{Ok, Result}; 
+/// use walkfile::walk;
 /// fn test_walk(){
 /// let res = walk(&PathBuf::from_str("./").unwrap());
 /// let res = match res {
@@ -75,45 +155,127 @@ impl std::fmt::Display for WalkFileEntry {
 /// } 
 /// ```
 pub fn walk(path: &PathBuf)->Result<Vec<WalkFileEntry>>{
+    walk_with(path, &WalkOptions::default())
+}
+
+/// 按照指定的选项遍历根目录
+/// ```rust
+/// use std::{path::PathBuf, str::FromStr};
+/// use walkfile::{walk_with, HiddenPolicy, WalkOptions};
+/// let options = WalkOptions{ hidden: HiddenPolicy::Exclude, ..Default::default() };
+/// let res = walk_with(&PathBuf::from_str("./").unwrap(), &options).unwrap();
+/// assert!(res.iter().all(|e| e.child_files.iter().all(|f| !f.starts_with('.'))));
+/// ```
+pub fn walk_with(path: &PathBuf, options: &WalkOptions)->Result<Vec<WalkFileEntry>>{
     let mut res:Vec<WalkFileEntry> = Vec::new();
-    walk_dir(path, &mut res)?;
+    walk_dir(path, path, options, &mut None, &DirFilter::new(path, options), &mut |entry| {
+        res.push(entry);
+        Ok(())
+    })?;
     Ok(res)
 }
 
+/// 获取指定根目录下所有文件的完整路径，按路径排序
+/// ```rust
+/// use std::{path::{Path, PathBuf}, str::FromStr};
+/// use walkfile::walk_files;
+/// let files = walk_files(&PathBuf::from_str("./").unwrap()).unwrap();
+/// assert!(files.iter().any(|f| f == Path::new("./src/lib.rs")));
+/// ```
+pub fn walk_files(path: &PathBuf)->Result<Vec<PathBuf>>{
+    let mut files = walk(path)?.iter().flat_map(|e| e.file_paths().collect::<Vec<_>>()).collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
 /// 递归的获取子文件夹， 子文件
-fn walk_dir(path: &PathBuf, res: &mut Vec<WalkFileEntry>)->Result<()>{
+/// visited不为None时跳过已经遍历过以及属于其他根目录的子文件夹，同一个文件夹只会遍历一次
+/// filter为上一级文件夹的过滤条件，进入文件夹后会加上其中忽略文件的规则
+/// 每个文件夹读取完（子文件夹在它之前）后交给each处理，each返回错误时停止遍历
+pub(crate) fn walk_dir(walk_root: &PathBuf, path: &PathBuf, options: &WalkOptions, visited: &mut Option<Visited>, filter: &DirFilter, each: &mut dyn FnMut(WalkFileEntry)->Result<()>)->Result<()>{
+    let relative = path.strip_prefix(walk_root).unwrap_or(path);
+    let filter = filter.enter(path, relative, options);
     let mut walk = WalkFileEntry{
+        walk_root: walk_root.clone(),
         root: path.clone(),
         child_dirs: vec![],
         child_files: vec![],
+        child_specials: vec![],
+        child_contents: HashMap::new(),
     };
     for entry in fs::read_dir(path)?{
         let entry = entry?;
-        if entry.path().is_dir(){
-            if let Some(p) = entry.path().file_name(){
-                if let Some(p_str) = p.to_str(){
-                    walk.child_dirs.push(p_str.to_string());
+        let name = match entry.file_name().to_str() {
+            None => continue,
+            Some(name) => name.to_string(),
+        };
+        let path = entry.path();
+        if !filter.allows(&name, &relative.join(&name), path.is_dir(), options){
+            continue;
+        }
+        if path.is_dir(){
+            if let Some(visited) = visited{
+                if !visited.first_visit(walk_root, &path)?{
+                    continue;
                 }
             }
-            walk_dir(&entry.path(), res)?;
-        }else if entry.path().is_file() {
-            if let Some(p) = entry.path().file_name(){
-                if let Some(p_file) = p.to_str(){
-                    walk.child_files.push(p_file.to_string());
+            walk.child_dirs.push(name);
+            walk_dir(walk_root, &path, options, visited, &filter, each)?;
+        }else if path.is_file() {
+            if options.sniff_content{
+                if let std::result::Result::Ok(content) = sniff_file(&path){
+                    walk.child_contents.insert(name.clone(), content);
                 }
             }
+            walk.child_files.push(name);
+        }else{
+            let kind = SpecialKind::from_path(&path);
+            walk.child_specials.push(SpecialFile{ name, kind });
         }
     }
-    res.push(walk);
-    Ok(())
+    each(walk)
 }
 
 #[cfg(test)]
-mod test{
-    use std::{path::PathBuf, str::FromStr};
+pub(crate) mod test{
+    use std::{fs, ops::Deref, path::{Path, PathBuf}, str::FromStr};
 
     use crate::walk;
 
+    /// # TempDir
+    /// 测试使用的临时文件夹，离开作用域时删除，断言失败导致panic时也会清理
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// 创建名称中带有进程id的空文件夹，路径是规范化后的
+        pub(crate) fn new(name: &str)->TempDir{
+            let dir = std::env::temp_dir().join(format!("walkfile_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(fs::canonicalize(dir).unwrap())
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
     #[test]
     fn test_walk(){
         let res = walk(&PathBuf::from_str("./").unwrap());
@@ -134,4 +296,112 @@ mod test{
         let (root, child_dirs, child_files) = res[0].as_tuple();
         println!("{:?}, {:?}, {:?}", root, child_dirs, child_files);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_and_hidden(){
+        use std::{fs, os::unix::net::UnixListener};
+        use crate::{walk_with, HiddenPolicy, SpecialKind, WalkOptions};
+
+        let dir = TempDir::new("special");
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".gitignore"), "").unwrap();
+        fs::write(dir.join("main.rs"), "").unwrap();
+        let _listener = UnixListener::bind(dir.join("app.sock")).unwrap();
+        std::os::unix::fs::symlink(dir.join("app.sock"), dir.join("link.sock")).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken")).unwrap();
+
+        let options = WalkOptions{ hidden: HiddenPolicy::ExcludeExcept(vec![".gitignore".to_string()]), ..Default::default() };
+        let res = walk_with(&dir, &options).unwrap();
+        assert_eq!(1, res.len());
+        let mut files = res[0].child_files.clone();
+        files.sort();
+        assert_eq!(vec![".gitignore".to_string(), "main.rs".to_string()], files);
+        assert!(res[0].child_dirs.is_empty());
+        let mut specials = res[0].child_specials.iter().map(|s| (s.name.as_str(), s.kind)).collect::<Vec<_>>();
+        specials.sort_by_key(|(name, _)| *name);
+        assert_eq!(vec![("app.sock", SpecialKind::Socket), ("broken", SpecialKind::BrokenSymlink), ("link.sock", SpecialKind::Socket)], specials);
+    }
+
+    #[test]
+    fn test_relative_paths(){
+        use std::fs;
+
+        let dir = TempDir::new("relative");
+        fs::create_dir_all(dir.join("src/bin")).unwrap();
+        fs::write(dir.join("src/bin/main.rs"), "").unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        let res = walk(&dir).unwrap();
+        let bin = res.iter().find(|e| e.relative_root() == Path::new("src/bin")).unwrap();
+        assert_eq!(vec![PathBuf::from("src/bin/main.rs")], bin.relative_file_paths().collect::<Vec<_>>());
+        assert_eq!(vec![dir.join("src/bin/main.rs")], bin.file_paths().collect::<Vec<_>>());
+        let top = res.iter().find(|e| e.root == *dir).unwrap();
+        assert_eq!(Path::new(""), top.relative_root());
+        assert_eq!(vec![PathBuf::from("src")], top.relative_dir_paths().collect::<Vec<_>>());
+        let files = crate::walk_files(&dir).unwrap();
+        assert_eq!(vec![dir.join("Cargo.toml"), dir.join("src/bin/main.rs")], files);
+    }
+
+    #[test]
+    fn test_sniff_content(){
+        use std::fs;
+        use crate::{walk_with, ContentType, Magic, WalkOptions};
+
+        let dir = TempDir::new("sniff");
+        fs::write(dir.join("run"), "#!/bin/bash\necho hi\n").unwrap();
+        fs::write(dir.join("logo.rs"), b"\x89PNG\r\n\x1a\n\x00\x00").unwrap();
+        let options = WalkOptions{ sniff_content: true, ..Default::default() };
+        let res = walk_with(&dir, &options).unwrap();
+        let plain = walk(&dir).unwrap();
+        assert_eq!(Some(&ContentType::Known(Magic::Png)), res[0].content_type("logo.rs"));
+        match res[0].content_type("run") {
+            Some(ContentType::Text { interpreter, .. }) => assert_eq!(Some("bash".to_string()), *interpreter),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(vec![dir.join("run")], res[0].text_file_paths().collect::<Vec<_>>());
+        assert!(plain[0].child_contents.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_files(){
+        use std::fs;
+        use crate::{walk_files, walk_with, Glob, WalkOptions, DEFAULT_IGNORE_FILES};
+
+        let dir = TempDir::new("ignore");
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::create_dir_all(dir.join("src/gen")).unwrap();
+        fs::write(dir.join(".gitignore"), "target/\n*.log\n/src/local.rs\n").unwrap();
+        fs::write(dir.join(".git/config"), "").unwrap();
+        fs::write(dir.join("target/debug/app"), "").unwrap();
+        fs::write(dir.join("src/.ignore"), "gen/\n!keep.log\n").unwrap();
+        fs::write(dir.join("src/gen/out.rs"), "").unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/local.rs"), "").unwrap();
+        fs::write(dir.join("src/debug.log"), "").unwrap();
+        fs::write(dir.join("src/main.min.rs"), "").unwrap();
+        fs::write(dir.join("src/keep.log"), "").unwrap();
+        fs::write(dir.join("debug.log"), "").unwrap();
+        let options = WalkOptions{
+            excludes: vec![Glob::new("*.min.rs")],
+            ignore_files: DEFAULT_IGNORE_FILES.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        };
+        let res = walk_with(&dir, &options).unwrap();
+        let all = walk_files(&dir).unwrap();
+        // 从子文件夹开始遍历时仍然遵守仓库根目录的忽略文件，不在仓库中时不读取上层的忽略文件
+        let names = |res: Vec<crate::WalkFileEntry>| res.iter().flat_map(|e| e.relative_file_paths().collect::<Vec<_>>()).collect::<std::collections::BTreeSet<_>>();
+        let src = names(walk_with(&dir.join("src"), &options).unwrap());
+        fs::remove_dir_all(dir.join(".git")).unwrap();
+        let plain = names(walk_with(&dir.join("src"), &options).unwrap());
+        let mut files = res.iter().flat_map(|e| e.relative_file_paths().collect::<Vec<_>>()).collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(vec![
+            PathBuf::from(".gitignore"), PathBuf::from("src/.ignore"),
+            PathBuf::from("src/keep.log"), PathBuf::from("src/main.rs"),
+        ], files);
+        assert_eq!(11, all.len());
+        assert_eq!(vec![".ignore", "keep.log", "main.rs"], src.iter().map(|p| p.to_str().unwrap()).collect::<Vec<_>>());
+        assert_eq!(vec![".ignore", "debug.log", "keep.log", "local.rs", "main.rs"], plain.iter().map(|p| p.to_str().unwrap()).collect::<Vec<_>>());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,258 @@
+use std::{fs::File, io::{self, Read}, path::Path};
+
+/// 判断文件类型时读取的字节数，tar的标记位于第257个字节，需要留出余量
+pub const SNIFF_LEN: usize = 1024;
+
+/// # Magic
+/// 通过文件头部的魔数识别出的二进制格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Magic{
+    Elf,
+    MachO,
+    Pe,
+    Wasm,
+    JavaClass,
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Webp,
+    Ico,
+    Pdf,
+    Gzip,
+    Zip,
+    Bzip2,
+    Xz,
+    Zstd,
+    SevenZip,
+    Tar,
+    Sqlite,
+}
+
+/// 魔数表：(偏移, 字节, 类型)，按顺序匹配
+const MAGICS: &[(usize, &[u8], Magic)] = &[
+    (0, b"\x7fELF", Magic::Elf),
+    (0, b"\xfe\xed\xfa\xce", Magic::MachO),
+    (0, b"\xfe\xed\xfa\xcf", Magic::MachO),
+    (0, b"\xce\xfa\xed\xfe", Magic::MachO),
+    (0, b"\xcf\xfa\xed\xfe", Magic::MachO),
+    (0, b"\xca\xfe\xba\xbe", Magic::JavaClass),
+    (0, b"\x00asm", Magic::Wasm),
+    (0, b"\x89PNG\r\n\x1a\n", Magic::Png),
+    (0, b"\xff\xd8\xff", Magic::Jpeg),
+    (0, b"GIF87a", Magic::Gif),
+    (0, b"GIF89a", Magic::Gif),
+    (0, b"\x00\x00\x01\x00", Magic::Ico),
+    (0, b"%PDF-", Magic::Pdf),
+    (0, b"\x1f\x8b", Magic::Gzip),
+    (0, b"PK\x03\x04", Magic::Zip),
+    (0, b"PK\x05\x06", Magic::Zip),
+    (0, b"BZh", Magic::Bzip2),
+    (0, b"\xfd7zXZ\x00", Magic::Xz),
+    (0, b"\x28\xb5\x2f\xfd", Magic::Zstd),
+    (0, b"7z\xbc\xaf\x27\x1c", Magic::SevenZip),
+    // POSIX的`ustar\000`与GNU的`ustar  \0`，只匹配`ustar`时含有`mustard`等单词的文本也会被当作tar
+    (257, b"ustar\x00", Magic::Tar),
+    (257, b"ustar  \x00", Magic::Tar),
+    (0, b"SQLite format 3\x00", Magic::Sqlite),
+];
+
+/// 魔数中的一处：(偏移, 字节)
+type MagicPart = (usize, &'static [u8]);
+
+/// 需要同时匹配多处的魔数，例如RIFF容器中的WebP，只匹配`WEBP`时第8个字节开始为`WEBP`的文本也会被当作图片
+const COMPOUND_MAGICS: &[(&[MagicPart], Magic)] = &[
+    (&[(0, b"RIFF"), (8, b"WEBP")], Magic::Webp),
+];
+
+/// 只有两个字节的魔数容易和普通文本冲突，只在内容含有NUL字节时匹配
+const WEAK_MAGICS: &[(usize, &[u8], Magic)] = &[
+    (0, b"MZ", Magic::Pe),
+    (0, b"BM", Magic::Bmp),
+];
+
+fn matches_at(bytes: &[u8], offset: usize, magic: &[u8])->bool{
+    bytes.len() >= offset + magic.len() && &bytes[offset..offset + magic.len()] == magic
+}
+
+fn find_magic(table: &[(usize, &[u8], Magic)], bytes: &[u8])->Option<Magic>{
+    table.iter()
+        .find(|(offset, magic, _)| matches_at(bytes, *offset, magic))
+        .map(|(_, _, magic)| *magic)
+}
+
+fn find_compound_magic(bytes: &[u8])->Option<Magic>{
+    COMPOUND_MAGICS.iter()
+        .find(|(parts, _)| parts.iter().all(|(offset, magic)| matches_at(bytes, *offset, magic)))
+        .map(|(_, magic)| *magic)
+}
+
+impl Magic {
+    pub fn is_image(&self)->bool{
+        matches!(self, Magic::Png | Magic::Jpeg | Magic::Gif | Magic::Bmp | Magic::Webp | Magic::Ico)
+    }
+
+    pub fn is_archive(&self)->bool{
+        matches!(self, Magic::Gzip | Magic::Zip | Magic::Bzip2 | Magic::Xz | Magic::Zstd | Magic::SevenZip | Magic::Tar)
+    }
+
+    pub fn is_executable(&self)->bool{
+        matches!(self, Magic::Elf | Magic::MachO | Magic::Pe | Magic::Wasm | Magic::JavaClass)
+    }
+}
+
+/// # TextEncoding
+/// * Utf8: 合法的UTF-8
+/// * Utf8Bom: 带BOM的UTF-8
+/// * Utf16Le/Utf16Be: 带BOM的UTF-16
+/// * Unknown: 不是UTF-8，但没有NUL字节且控制字符很少，例如Latin-1、GBK
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextEncoding{
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Unknown,
+}
+
+/// # ContentType
+/// 根据文件内容判断出的类型
+/// * Empty: 空文件
+/// * Text: 文本文件，interpreter为shebang中的解释器名称，例如`#!/usr/bin/env python3`中的`python3`
+/// * Known: 能识别魔数的二进制文件
+/// * Binary: 其他二进制文件
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContentType{
+    Empty,
+    Text{ encoding: TextEncoding, interpreter: Option<String> },
+    Known(Magic),
+    Binary,
+}
+
+impl ContentType {
+    pub fn is_text(&self)->bool{
+        matches!(self, ContentType::Empty | ContentType::Text { .. })
+    }
+
+    pub fn is_binary(&self)->bool{
+        !self.is_text()
+    }
+}
+
+/// 根据文件开头的字节判断类型
+/// ```rust
+/// use walkfile::{sniff, ContentType, Magic, TextEncoding};
+/// assert_eq!(ContentType::Known(Magic::Png), sniff(b"\x89PNG\r\n\x1a\n...."));
+/// assert_eq!(
+///     ContentType::Text{ encoding: TextEncoding::Utf8, interpreter: Some("python3".to_string()) },
+///     sniff(b"#!/usr/bin/env python3\nprint(1)\n"),
+/// );
+/// ```
+pub fn sniff(bytes: &[u8])->ContentType{
+    if bytes.is_empty(){
+        return ContentType::Empty;
+    }
+    if let Some(magic) = find_magic(MAGICS, bytes).or_else(|| find_compound_magic(bytes)){
+        return ContentType::Known(magic);
+    }
+    let (encoding, body) = if let Some(body) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        (TextEncoding::Utf8Bom, body)
+    }else if bytes.starts_with(b"\xff\xfe") {
+        return ContentType::Text{ encoding: TextEncoding::Utf16Le, interpreter: None };
+    }else if bytes.starts_with(b"\xfe\xff") {
+        return ContentType::Text{ encoding: TextEncoding::Utf16Be, interpreter: None };
+    }else{
+        (TextEncoding::Utf8, bytes)
+    };
+    if body.contains(&0){
+        return match find_magic(WEAK_MAGICS, bytes) {
+            Some(magic) => ContentType::Known(magic),
+            None => ContentType::Binary,
+        };
+    }
+    let encoding = match std::str::from_utf8(body) {
+        Ok(_) => encoding,
+        // 缓冲区截断在多字节字符中间，不算错误
+        Err(e) if e.error_len().is_none() => encoding,
+        Err(_) => {
+            let control = body.iter().filter(|b| b.is_ascii_control() && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)).count();
+            if control * 10 > body.len(){
+                return ContentType::Binary;
+            }
+            TextEncoding::Unknown
+        }
+    };
+    ContentType::Text{ encoding, interpreter: shebang_interpreter(body) }
+}
+
+/// 读取文件开头的字节并判断类型
+pub fn sniff_file(path: &Path)->io::Result<ContentType>{
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut buf)?;
+    io::Result::Ok(sniff(&buf))
+}
+
+/// 解析shebang中的解释器，`/usr/bin/env`会跳过选项取后面的程序名
+pub fn shebang_interpreter(bytes: &[u8])->Option<String>{
+    let rest = bytes.strip_prefix(b"#!")?;
+    let line = rest.split(|b| *b == b'\n').next()?;
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split_whitespace();
+    let mut program = parts.next()?.rsplit('/').next()?;
+    if program == "env"{
+        program = parts.find(|p| !p.starts_with('-') && !p.contains('='))?;
+    }
+    if program.is_empty(){
+        return None;
+    }
+    Some(program.to_string())
+}
+
+#[cfg(test)]
+mod test{
+    use super::{shebang_interpreter, sniff, ContentType, Magic, TextEncoding};
+
+    #[test]
+    fn test_magic(){
+        assert_eq!(ContentType::Known(Magic::Elf), sniff(b"\x7fELF\x02\x01\x01\x00"));
+        assert_eq!(ContentType::Known(Magic::Gzip), sniff(b"\x1f\x8b\x08\x00"));
+        assert_eq!(ContentType::Known(Magic::Zip), sniff(b"PK\x03\x04\x14\x00"));
+        assert_eq!(ContentType::Known(Magic::Pdf), sniff(b"%PDF-1.7\n"));
+        let mut tar = vec![b'a'; 300];
+        tar[257..263].copy_from_slice(b"ustar\x00");
+        assert_eq!(ContentType::Known(Magic::Tar), sniff(&tar));
+        tar[257..265].copy_from_slice(b"ustar  \x00");
+        assert_eq!(ContentType::Known(Magic::Tar), sniff(&tar));
+        assert_eq!(ContentType::Known(Magic::Webp), sniff(b"RIFF\x24\x00\x00\x00WEBPVP8 "));
+        // 只是恰好在相同位置出现这些单词的文本
+        let mut text = format!("{}\n", "/".repeat(255)).into_bytes();
+        text.extend_from_slice(b"mustard is not a tar header\n");
+        assert_eq!(b"ustar", &text[257..262]);
+        assert!(sniff(&text).is_text());
+        assert_eq!(b"WEBP", &b"// VP8: WEBP encoder\n"[8..12]);
+        assert!(sniff(b"// VP8: WEBP encoder\nfn main(){}\n").is_text());
+        assert!(Magic::Png.is_image());
+        assert_eq!(ContentType::Known(Magic::Pe), sniff(b"MZ\x90\x00\x03\x00"));
+        assert!(sniff(b"BMW and MZ are just text\n").is_text());
+    }
+
+    #[test]
+    fn test_text(){
+        assert_eq!(ContentType::Empty, sniff(b""));
+        assert_eq!(ContentType::Text{ encoding: TextEncoding::Utf8, interpreter: None }, sniff("fn main(){}\n// 注释".as_bytes()));
+        assert_eq!(ContentType::Text{ encoding: TextEncoding::Utf8Bom, interpreter: None }, sniff(b"\xef\xbb\xbfhello"));
+        assert_eq!(ContentType::Text{ encoding: TextEncoding::Unknown, interpreter: None }, sniff(b"caf\xe9 au lait\n"));
+        // 截断在多字节字符中间
+        assert!(sniff(&"中文".as_bytes()[..4]).is_text());
+        assert_eq!(ContentType::Binary, sniff(b"abc\x00def"));
+        assert!(sniff(b"\x01\x02\x03\x04\x05\xff").is_binary());
+    }
+
+    #[test]
+    fn test_shebang(){
+        assert_eq!(Some("sh".to_string()), shebang_interpreter(b"#!/bin/sh\n"));
+        assert_eq!(Some("python3".to_string()), shebang_interpreter(b"#! /usr/bin/env python3\n"));
+        assert_eq!(Some("node".to_string()), shebang_interpreter(b"#!/usr/bin/env -S NODE_ENV=1 node --flag\n"));
+        assert_eq!(None, shebang_interpreter(b"# comment\n"));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fs, path::Path};
+
+/// # HiddenPolicy
+/// 以`.`开头的文件、文件夹的处理策略
+/// * Include: 全部保留（默认）
+/// * Exclude: 全部跳过
+/// * ExcludeExcept: 跳过，但保留列出的名称，例如`.github`、`.gitignore`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HiddenPolicy{
+    #[default]
+    Include,
+    Exclude,
+    ExcludeExcept(Vec<String>),
+}
+
+impl HiddenPolicy {
+    /// 判断名称为`name`的条目是否应该被保留
+    /// ```rust
+    /// use walkfile::HiddenPolicy;
+    /// let policy = HiddenPolicy::ExcludeExcept(vec![".github".to_string()]);
+    /// assert!(policy.allows("src"));
+    /// assert!(policy.allows(".github"));
+    /// assert!(!policy.allows(".git"));
+    /// ```
+    pub fn allows(&self, name: &str)->bool{
+        if !name.starts_with('.'){
+            return true;
+        }
+        match self {
+            HiddenPolicy::Include => true,
+            HiddenPolicy::Exclude => false,
+            HiddenPolicy::ExcludeExcept(keep) => keep.iter().any(|k| k == name),
+        }
+    }
+}
+
+/// 以.开头的文件为隐藏文件，`.`与`..`不算
+pub fn is_hidden(path: &Path)->bool{
+    match path.file_name().and_then(|p| p.to_str()) {
+        None => false,
+        Some(name) => name.starts_with('.'),
+    }
+}
+
+/// # SpecialKind
+/// 既不是文件也不是文件夹的条目类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpecialKind{
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
+    /// 指向不存在目标的符号链接
+    BrokenSymlink,
+    Other,
+}
+
+impl SpecialKind {
+    /// 根据条目的路径进行分类，符号链接按照指向的目标分类，目标不存在时为BrokenSymlink
+    pub fn from_path(path: &Path)->SpecialKind{
+        match fs::metadata(path) {
+            Err(_) => SpecialKind::BrokenSymlink,
+            Ok(metadata) => SpecialKind::from_file_type(&metadata.file_type()),
+        }
+    }
+
+    /// 根据文件类型进行分类，符号链接本身无法判断目标的类型，总是Other
+    pub fn from_file_type(file_type: &fs::FileType)->SpecialKind{
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_socket(){
+                return SpecialKind::Socket;
+            }
+            if file_type.is_fifo(){
+                return SpecialKind::Fifo;
+            }
+            if file_type.is_block_device(){
+                return SpecialKind::BlockDevice;
+            }
+            if file_type.is_char_device(){
+                return SpecialKind::CharDevice;
+            }
+        }
+        SpecialKind::Other
+    }
+}
+
+/// # SpecialFile
+/// * name: 条目名称
+/// * kind: 条目类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecialFile{
+    pub name: String,
+    pub kind: SpecialKind,
+}
+
+#[cfg(test)]
+mod test{
+    use std::{path::PathBuf, str::FromStr};
+
+    use super::{is_hidden, HiddenPolicy};
+
+    #[test]
+    fn test_is_hidden(){
+        assert!(is_hidden(&PathBuf::from_str("./.vimrc").unwrap()));
+        assert!(!is_hidden(&PathBuf::from_str("./src").unwrap()));
+        assert!(!is_hidden(&PathBuf::from_str("./").unwrap()));
+        assert!(!is_hidden(&PathBuf::from_str("..").unwrap()));
+    }
+
+    #[test]
+    fn test_policy(){
+        assert!(HiddenPolicy::Include.allows(".git"));
+        assert!(!HiddenPolicy::Exclude.allows(".git"));
+        assert!(HiddenPolicy::Exclude.allows("main.rs"));
+        let policy = HiddenPolicy::ExcludeExcept(vec![".gitignore".to_string()]);
+        assert!(policy.allows(".gitignore"));
+        assert!(!policy.allows(".git"));
+    }
+}
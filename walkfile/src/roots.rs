@@ -0,0 +1,152 @@
+use std::{collections::HashSet, fs, path::{Path, PathBuf}};
+use anyhow::{Context, Ok, Result};
+
+use crate::{ignore::DirFilter, walk_dir, WalkFileEntry, WalkOptions};
+
+/// 规范化多个根目录：转换为绝对路径、去掉重复的根目录以及嵌套在其他根目录中的根目录，按路径排序
+/// ```rust
+/// use std::path::PathBuf;
+/// use walkfile::normalize_roots;
+/// let roots = normalize_roots(&[PathBuf::from("./src"), PathBuf::from("./"), PathBuf::from(".")]).unwrap();
+/// assert_eq!(1, roots.len());
+/// ```
+pub fn normalize_roots(paths: &[PathBuf])->Result<Vec<PathBuf>>{
+    let mut roots = Vec::with_capacity(paths.len());
+    for path in paths{
+        let root = fs::canonicalize(path).with_context(|| format!("根目录{}不存在", path.display()))?;
+        roots.push(root);
+    }
+    // 排序后父目录一定排在子目录之前
+    roots.sort();
+    let mut res: Vec<PathBuf> = Vec::with_capacity(roots.len());
+    for root in roots{
+        if res.iter().any(|kept| root.starts_with(kept)){
+            continue;
+        }
+        res.push(root);
+    }
+    Ok(res)
+}
+
+/// # Visited
+/// 同时遍历多个根目录时记录的状态
+/// * roots: 规范化后的所有根目录
+/// * dirs: 已经遍历过的文件夹（规范化后的路径）
+pub(crate) struct Visited{
+    roots: Vec<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl Visited {
+    /// 判断walk_root下的文件夹是否需要遍历，已经遍历过的文件夹以及（通过符号链接）属于其他根目录的文件夹不需要遍历
+    pub(crate) fn first_visit(&mut self, walk_root: &Path, path: &Path)->Result<bool>{
+        let dir = fs::canonicalize(path)?;
+        if self.roots.iter().any(|root| root != walk_root && dir.starts_with(root)){
+            return Ok(false);
+        }
+        Ok(self.dirs.insert(dir))
+    }
+}
+
+/// 同时遍历多个根目录
+///
+/// 根目录会先经过`normalize_roots`处理，之后通过符号链接等方式再次到达的文件夹不会重复遍历。
+/// 每个WalkFileEntry的walk_root为它所属的（规范化后的）根目录，符号链接指向其他根目录时由那个根目录遍历
+/// ```rust
+/// use std::path::PathBuf;
+/// use walkfile::{walk_roots, WalkOptions};
+/// let res = walk_roots(&[PathBuf::from("./src"), PathBuf::from("./")], &WalkOptions::default()).unwrap();
+/// let root = std::fs::canonicalize("./").unwrap();
+/// assert!(res.iter().all(|e| e.walk_root == root));
+/// ```
+pub fn walk_roots(paths: &[PathBuf], options: &WalkOptions)->Result<Vec<WalkFileEntry>>{
+    let mut res: Vec<WalkFileEntry> = Vec::new();
+    walk_roots_each(paths, options, |entry| {
+        res.push(entry);
+        Ok(())
+    })?;
+    Ok(res)
+}
+
+/// 与`walk_roots`相同，但是每读取完一个文件夹就交给each处理，不需要等待整个遍历结束，
+/// 子文件夹的WalkFileEntry在父文件夹之前，each返回错误时停止遍历并返回该错误
+/// ```rust
+/// use std::path::PathBuf;
+/// use walkfile::{walk_roots_each, WalkOptions};
+/// let mut files = 0;
+/// walk_roots_each(&[PathBuf::from("./src")], &WalkOptions::default(), |entry| {
+///     files += entry.child_files.len();
+///     Ok(())
+/// }).unwrap();
+/// assert!(files > 0);
+/// ```
+pub fn walk_roots_each(paths: &[PathBuf], options: &WalkOptions, mut each: impl FnMut(WalkFileEntry)->Result<()>)->Result<()>{
+    let roots = normalize_roots(paths)?;
+    let mut visited = Some(Visited{ roots: roots.clone(), dirs: roots.iter().cloned().collect() });
+    for root in roots{
+        walk_dir(&root, &root, options, &mut visited, &DirFilter::new(&root, options), &mut each)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test{
+    use std::fs;
+
+    use super::{normalize_roots, walk_roots};
+    use crate::{test::TempDir, WalkOptions};
+
+    fn make_tree(name: &str)->TempDir{
+        let dir = TempDir::new(&format!("roots_{}", name));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::create_dir_all(dir.join("tests")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        fs::write(dir.join("src/nested/mod.rs"), "").unwrap();
+        fs::write(dir.join("tests/it.rs"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_normalize_roots(){
+        let dir = make_tree("normalize");
+        let roots = normalize_roots(&[dir.join("tests"), dir.join("src/nested"), dir.join("src"), dir.join("src/../src")]).unwrap();
+        assert!(normalize_roots(&[dir.join("missing")]).is_err());
+        assert_eq!(vec![dir.join("src"), dir.join("tests")], roots);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_roots_dedupe(){
+        let dir = make_tree("dedupe");
+        // tests/link指向src，src只应该被遍历一次
+        std::os::unix::fs::symlink(dir.join("src"), dir.join("tests/link")).unwrap();
+        let res = walk_roots(&[dir.join("src"), dir.join("tests")], &WalkOptions::default()).unwrap();
+        let mut roots = res.iter().map(|e| (e.walk_root.clone(), e.root.clone())).collect::<Vec<_>>();
+        roots.sort();
+        assert_eq!(vec![
+            (dir.join("src"), dir.join("src")),
+            (dir.join("src"), dir.join("src/nested")),
+            (dir.join("tests"), dir.join("tests")),
+        ], roots);
+        let tests = res.iter().find(|e| e.root == dir.join("tests")).unwrap();
+        assert!(tests.child_dirs.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_roots_symlink_into_root(){
+        let dir = make_tree("into_root");
+        // src先于tests遍历，src/link指向的tests仍然属于tests
+        std::os::unix::fs::symlink(dir.join("tests"), dir.join("src/link")).unwrap();
+        let res = walk_roots(&[dir.join("tests"), dir.join("src")], &WalkOptions::default()).unwrap();
+        let mut roots = res.iter().map(|e| (e.walk_root.clone(), e.root.clone())).collect::<Vec<_>>();
+        roots.sort();
+        assert_eq!(vec![
+            (dir.join("src"), dir.join("src")),
+            (dir.join("src"), dir.join("src/nested")),
+            (dir.join("tests"), dir.join("tests")),
+        ], roots);
+        let src = res.iter().find(|e| e.root == dir.join("src")).unwrap();
+        assert_eq!(vec!["nested".to_string()], src.child_dirs);
+    }
+}
@@ -0,0 +1,196 @@
+use std::{fs, io, path::Path};
+use anyhow::Result;
+
+use crate::{ignore::DirFilter, SpecialKind, WalkOptions};
+
+/// # WalkControl
+/// 访问者钩子的返回值，用来控制遍历的走向
+/// * Continue: 继续遍历
+/// * SkipSubtree: 在`enter_dir`中返回时不进入该文件夹；在`visit_file`、`visit_special`中返回时跳过所在文件夹剩余的条目；
+///   在`leave_dir`中返回时跳过上一级文件夹剩余的条目
+/// * Stop: 立即结束整个遍历
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkControl{
+    Continue,
+    SkipSubtree,
+    Stop,
+}
+
+/// # Visitor
+/// 遍历时的回调，所有钩子都有默认实现，只需要实现关心的部分
+///
+/// 调用顺序：
+/// 1. `enter_dir(dir)`
+/// 2. dir下的条目按名称排序后依次处理：文件调用`visit_file`，特殊文件调用`visit_special`，文件夹递归执行1~3
+/// 3. `leave_dir(dir)`，只有`enter_dir`返回`Continue`的文件夹才会调用，此时该文件夹的子树已全部处理完毕
+///
+/// 读取文件夹失败时调用`error`，默认返回`Stop`，此时`walk_visit`会返回这个错误
+pub trait Visitor {
+    fn enter_dir(&mut self, _path: &Path)->WalkControl{
+        WalkControl::Continue
+    }
+
+    fn visit_file(&mut self, _path: &Path)->WalkControl{
+        WalkControl::Continue
+    }
+
+    fn visit_special(&mut self, _path: &Path, _kind: SpecialKind)->WalkControl{
+        WalkControl::Continue
+    }
+
+    fn leave_dir(&mut self, _path: &Path)->WalkControl{
+        WalkControl::Continue
+    }
+
+    fn error(&mut self, _path: &Path, _err: &io::Error)->WalkControl{
+        WalkControl::Stop
+    }
+}
+
+/// 以访问者的方式遍历指定根目录
+/// ```rust
+/// use std::path::Path;
+/// use walkfile::{walk_visit, Visitor, WalkControl, WalkOptions};
+///
+/// struct CountFiles(usize);
+/// impl Visitor for CountFiles {
+///     fn visit_file(&mut self, _path: &Path)->WalkControl{
+///         self.0 += 1;
+///         WalkControl::Continue
+///     }
+/// }
+/// let mut visitor = CountFiles(0);
+/// walk_visit(Path::new("./"), &WalkOptions::default(), &mut visitor).unwrap();
+/// assert!(visitor.0 > 0);
+/// ```
+pub fn walk_visit<V: Visitor>(path: &Path, options: &WalkOptions, visitor: &mut V)->Result<()>{
+    match visitor.enter_dir(path) {
+        WalkControl::Continue => {},
+        WalkControl::SkipSubtree | WalkControl::Stop => return Ok(()),
+    }
+    visit_dir(path, path, options, &DirFilter::new(path, options), visitor)?;
+    Ok(())
+}
+
+/// 处理已经进入的文件夹，返回`Stop`表示整个遍历需要结束
+fn visit_dir<V: Visitor>(root: &Path, path: &Path, options: &WalkOptions, filter: &DirFilter, visitor: &mut V)->Result<WalkControl>{
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let filter = filter.enter(path, relative, options);
+    let entries = match read_sorted(path) {
+        Err(e) => return match visitor.error(path, &e) {
+            WalkControl::Stop => Err(e.into()),
+            _ => Ok(visitor.leave_dir(path)),
+        },
+        Ok(entries) => entries,
+    };
+    for entry in entries{
+        let name = match entry.file_name().to_str() {
+            None => continue,
+            Some(name) => name.to_string(),
+        };
+        let child = entry.path();
+        if !filter.allows(&name, &relative.join(&name), child.is_dir(), options){
+            continue;
+        }
+        let control = if child.is_dir(){
+            match visitor.enter_dir(&child) {
+                WalkControl::Continue => visit_dir(root, &child, options, &filter, visitor)?,
+                WalkControl::SkipSubtree => WalkControl::Continue,
+                WalkControl::Stop => WalkControl::Stop,
+            }
+        }else if child.is_file(){
+            visitor.visit_file(&child)
+        }else{
+            visitor.visit_special(&child, SpecialKind::from_path(&child))
+        };
+        match control {
+            WalkControl::Continue => {},
+            WalkControl::SkipSubtree => break,
+            WalkControl::Stop => return Ok(WalkControl::Stop),
+        }
+    }
+    Ok(visitor.leave_dir(path))
+}
+
+/// 读取文件夹下的条目并按名称排序，保证遍历顺序稳定
+fn read_sorted(path: &Path)->io::Result<Vec<fs::DirEntry>>{
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    io::Result::Ok(entries)
+}
+
+#[cfg(test)]
+mod test{
+    use std::{fs, path::{Path, PathBuf}};
+
+    use super::{walk_visit, Visitor, WalkControl};
+    use crate::{test::TempDir, WalkOptions};
+
+    struct Recorder{
+        root: PathBuf,
+        events: Vec<String>,
+        skip: &'static str,
+    }
+
+    impl Recorder {
+        fn name(&self, path: &Path)->String{
+            path.strip_prefix(&self.root).unwrap().display().to_string()
+        }
+    }
+
+    impl Visitor for Recorder {
+        fn enter_dir(&mut self, path: &Path)->WalkControl{
+            let name = self.name(path);
+            self.events.push(format!("enter {}", name));
+            if name == self.skip{
+                return WalkControl::SkipSubtree;
+            }
+            WalkControl::Continue
+        }
+
+        fn visit_file(&mut self, path: &Path)->WalkControl{
+            let name = self.name(path);
+            self.events.push(format!("file {}", name));
+            if name == "b/stop.txt"{
+                return WalkControl::Stop;
+            }
+            WalkControl::Continue
+        }
+
+        fn leave_dir(&mut self, path: &Path)->WalkControl{
+            let name = self.name(path);
+            self.events.push(format!("leave {}", name));
+            WalkControl::Continue
+        }
+    }
+
+    fn make_tree(name: &str)->TempDir{
+        let dir = TempDir::new(&format!("visitor_{}", name));
+        fs::create_dir_all(dir.join("a/inner")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a/inner/x.txt"), "").unwrap();
+        fs::write(dir.join("a/y.txt"), "").unwrap();
+        fs::write(dir.join("b/stop.txt"), "").unwrap();
+        fs::write(dir.join("b/z.txt"), "").unwrap();
+        fs::write(dir.join("c.txt"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_order_and_skip(){
+        let dir = make_tree("skip");
+        let mut visitor = Recorder{ root: dir.clone(), events: vec![], skip: "a/inner" };
+        walk_visit(&dir, &WalkOptions::default(), &mut visitor).unwrap();
+        assert_eq!(vec![
+            "enter ", "enter a", "enter a/inner", "file a/y.txt", "leave a",
+            "enter b", "file b/stop.txt",
+        ], visitor.events);
+    }
+
+    #[test]
+    fn test_error(){
+        let dir = std::env::temp_dir().join("walkfile_visitor_not_exists");
+        let mut visitor = Recorder{ root: dir.clone(), events: vec![], skip: "-" };
+        assert!(walk_visit(&dir, &WalkOptions::default(), &mut visitor).is_err());
+    }
+}